@@ -0,0 +1,602 @@
+use crate::config::{Config, KeyDistribution, Workload};
+use crate::error::ErrorKind;
+use crate::keys::KeySampler;
+use crate::schema::{BatchInsert, Value};
+use crate::stats::{Operation, Stats};
+use crate::Driver;
+use anyhow::{anyhow, Result};
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Describes a single run of worker tasks
+pub struct Phase {
+    pub workload: Workload,
+    // Instead of the workload insert the rows it operates on, task number `i` inserts row number `i`
+    pub prefill: bool,
+    pub concurrency: i64,
+    // Number of tasks to perform, keys are taken from `0..config.key_count()` shifted by `key_offset`
+    pub tasks: i64,
+    // Added to the keys of the tasks, random keys of selects with a read ratio aren't shifted
+    pub key_offset: i64,
+    pub distribution: KeyDistribution,
+    // Tasks started per second, None means that each worker starts the next task right away
+    pub rate: Option<f64>,
+    // Run until the time runs out instead of performing `config.tasks` tasks
+    pub duration: Option<Duration>,
+}
+
+impl Phase {
+    // The measured part of the benchmark
+    pub fn benchmark(config: &Config) -> Phase {
+        Phase {
+            workload: config.workload,
+            prefill: false,
+            concurrency: config.concurrency,
+            tasks: config.tasks,
+            key_offset: 0,
+            distribution: config.distribution,
+            rate: config.rate,
+            duration: config.duration,
+        }
+    }
+
+    // Same work as the benchmark, but with its own length.
+    // Workloads inserting new rows use the keys after the ones of the benchmark, so that
+    // the benchmark doesn't overwrite rows inserted by the warmup.
+    pub fn warmup(config: &Config) -> Option<Phase> {
+        if config.warmup_tasks.is_none() && config.warmup_duration.is_none() {
+            return None;
+        }
+
+        let key_offset = match config.workload {
+            Workload::Inserts | Workload::Mixed | Workload::Batches => config.key_count(),
+            _ => 0,
+        };
+
+        Some(Phase {
+            tasks: config.warmup_tasks.unwrap_or(config.tasks),
+            key_offset,
+            duration: config.warmup_duration,
+            ..Phase::benchmark(config)
+        })
+    }
+}
+
+// Spawns `phase.concurrency` tasks which together perform `phase.tasks` tasks of the given workload.
+// Keys are chosen from `0..config.key_count()` according to `phase.distribution` and shifted by
+// `phase.key_offset`. The sequential distribution gives task number `i` the key `pk = i % key_count`,
+// so with a duration keys wrap around.
+// With a read ratio a mixed or timeseries task is either an insert of `pk` or a select of a random existing key.
+// With a rate task number `i` is scheduled to start `i / rate` seconds after the beginning
+// and its latency is measured from that moment, so that queueing delays aren't hidden.
+// Failed requests are counted in the stats, the phase fails only after `config.max_errors` errors.
+pub async fn run_tasks<D: Driver>(
+    driver: &Arc<D>,
+    config: &Arc<Config>,
+    phase: Phase,
+) -> Result<Stats> {
+    let mut handles = Vec::with_capacity(phase.concurrency.try_into().unwrap());
+    let next_batch_start = Arc::new(AtomicI64::new(0));
+    let error_count = Arc::new(AtomicU64::new(0));
+    let start_time = Instant::now();
+    let deadline: Option<Instant> = phase.duration.map(|duration| start_time + duration);
+    let keys = Arc::new(KeySampler::new(phase.distribution, config.key_count())?);
+    let phase = Arc::new(phase);
+
+    for worker in 0..phase.concurrency {
+        let driver = driver.clone();
+        let config = config.clone();
+        let next_batch_start = next_batch_start.clone();
+        let error_count = error_count.clone();
+        let phase = phase.clone();
+        let keys = keys.clone();
+
+        handles.push(tokio::spawn(async move {
+            let mut stats = Stats::default();
+            let mut rng = match config.seed {
+                Some(seed) => SmallRng::seed_from_u64(seed.wrapping_add(worker as u64)),
+                None => SmallRng::from_entropy(),
+            };
+
+            'worker: loop {
+                if too_many_errors(&config, &error_count) {
+                    break;
+                }
+
+                let cur_batch_start: i64 =
+                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
+
+                if deadline.is_none() && cur_batch_start >= phase.tasks {
+                    // No more work to do
+                    break;
+                }
+
+                let cur_batch_end: i64 = match deadline {
+                    Some(_) => cur_batch_start + config.batch_size,
+                    None => std::cmp::min(cur_batch_start + config.batch_size, phase.tasks),
+                };
+
+                for task in cur_batch_start..cur_batch_end {
+                    let pk = keys.key(task, &mut rng) + phase.key_offset;
+
+                    // Requests of the task, a failed request skips the rest
+                    let requests: [Option<Request>; 2] = match (phase.workload, config.read_ratio) {
+                        _ if phase.prefill => [Some(Request::prefill(&config, task)), None],
+                        (Workload::Inserts, _) => [Some(Request::Insert(pk)), None],
+                        (Workload::Selects, _) => [Some(Request::Select(pk)), None],
+                        (Workload::Mixed, None) => {
+                            [Some(Request::Insert(pk)), Some(Request::Select(pk))]
+                        }
+                        (Workload::Mixed, Some(read_ratio)) => {
+                            if rng.gen_bool(read_ratio) {
+                                [Some(Request::Select(keys.random_key(&mut rng))), None]
+                            } else {
+                                [Some(Request::Insert(pk)), None]
+                            }
+                        }
+                        (Workload::Updates, _) => [Some(Request::Update(pk)), None],
+                        (Workload::Deletes, _) => [
+                            Some(Request::Delete(pk)),
+                            config.verify_deletes.then_some(Request::SelectDeleted(pk)),
+                        ],
+                        (Workload::Scans, _) => [Some(Request::Scan(pk)), None],
+                        (Workload::Batches, _) => [Some(Request::Batch(pk)), None],
+                        (Workload::LwtInserts, _) => [Some(Request::LwtInsert(pk)), None],
+                        (Workload::LwtUpdates, _) => [Some(Request::LwtUpdate(pk)), None],
+                        (Workload::Counters, _) => [Some(Request::CounterUpdate(pk)), None],
+                        (Workload::Timeseries, None) => [
+                            Some(Request::InsertPoint { sensor: pk, age: 0 }),
+                            Some(Request::SelectPoints(pk)),
+                        ],
+                        (Workload::Timeseries, Some(read_ratio)) => {
+                            if rng.gen_bool(read_ratio) {
+                                [Some(Request::SelectPoints(keys.random_key(&mut rng))), None]
+                            } else {
+                                [Some(Request::InsertPoint { sensor: pk, age: 0 }), None]
+                            }
+                        }
+                    };
+
+                    // Values are generated before the requests are timed
+                    let requests: Vec<(Request, Values)> = requests
+                        .iter()
+                        .flatten()
+                        .map(|request| (*request, request.values(&config)))
+                        .collect();
+
+                    let mut request_start = Instant::now();
+
+                    if let Some(rate) = phase.rate {
+                        let scheduled_start =
+                            start_time + Duration::from_secs_f64(task as f64 / rate);
+
+                        if deadline.is_some_and(|deadline| scheduled_start >= deadline) {
+                            break 'worker;
+                        }
+
+                        tokio::time::sleep_until(scheduled_start.into()).await;
+
+                        stats.record_schedule_lag(scheduled_start.elapsed());
+                        request_start = scheduled_start;
+                    } else if deadline.is_some_and(|deadline| request_start >= deadline) {
+                        break 'worker;
+                    }
+
+                    for (request, values) in &requests {
+                        let response = match request.send(&*driver, &config, values).await {
+                            Ok(response) => response,
+                            Err(error) => {
+                                let kind = D::classify_error(&error);
+                                count_error(kind, &error, &error_count);
+                                stats.record_error(request.operation(), kind);
+                                break;
+                            }
+                        };
+                        let latency = request_start.elapsed();
+
+                        // A request which returned wrong rows is only counted as an error,
+                        // so it isn't included in the operations and latencies
+                        match request.verify(&config, values, response) {
+                            Ok(Some(applied)) => {
+                                stats.record(request.operation(), latency);
+                                stats.record_applied(request.operation(), applied);
+                            }
+                            Ok(None) => stats.record(request.operation(), latency),
+                            Err(error) => {
+                                count_error(ErrorKind::Verification, &error, &error_count);
+                                stats.record_error(request.operation(), ErrorKind::Verification);
+                                break;
+                            }
+                        }
+
+                        request_start = Instant::now();
+                    }
+                }
+            }
+
+            Ok::<Stats, anyhow::Error>(stats)
+        }));
+    }
+
+    let mut stats = Stats::default();
+    for handle in handles {
+        stats.merge(&handle.await??);
+    }
+
+    if too_many_errors(config, &error_count) {
+        return Err(anyhow!(
+            "Aborting after {} failed requests (--max-errors {})",
+            error_count.load(Ordering::Relaxed),
+            config.max_errors.unwrap_or_default()
+        ));
+    }
+
+    Ok(stats)
+}
+
+// Value added to a counter by every task of the counters workload
+const COUNTER_INCREMENT: i64 = 1;
+
+const MS_PER_DAY: i64 = 24 * 3600 * 1000;
+
+fn now_ms() -> i64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    since_epoch.as_millis() as i64
+}
+
+#[derive(Clone, Copy)]
+enum Request {
+    Insert(i64),
+    Select(i64),
+    Update(i64),
+    Delete(i64),
+    // Select of a deleted row, which shouldn't return anything
+    SelectDeleted(i64),
+    InsertWide { pk: i64, ck: i64 },
+    Scan(i64),
+    Batch(i64),
+    LwtInsert(i64),
+    LwtUpdate(i64),
+    CounterUpdate(i64),
+    // A point of the sensor with a timestamp `age` milliseconds before now
+    InsertPoint { sensor: i64, age: i64 },
+    SelectPoints(i64),
+}
+
+impl Request {
+    // Inserts row number `row` of the table used by the workload
+    fn prefill(config: &Config, row: i64) -> Request {
+        match config.workload {
+            Workload::Scans => Request::InsertWide {
+                pk: row / config.rows_per_partition,
+                ck: row % config.rows_per_partition,
+            },
+            Workload::Timeseries => Request::InsertPoint {
+                sensor: row / config.points_per_read as i64,
+                age: row % config.points_per_read as i64,
+            },
+            _ => Request::Insert(row),
+        }
+    }
+
+    fn operation(&self) -> Operation {
+        match self {
+            Request::Insert(_) | Request::InsertWide { .. } | Request::InsertPoint { .. } => {
+                Operation::Insert
+            }
+            Request::Select(_) | Request::SelectDeleted(_) | Request::SelectPoints(_) => {
+                Operation::Select
+            }
+            Request::Update(_) => Operation::Update,
+            Request::Delete(_) => Operation::Delete,
+            Request::Scan(_) => Operation::Scan,
+            Request::Batch(_) => Operation::Batch,
+            Request::LwtInsert(_) => Operation::LwtInsert,
+            Request::LwtUpdate(_) => Operation::LwtUpdate,
+            Request::CounterUpdate(_) => Operation::CounterUpdate,
+        }
+    }
+
+    // Values written by the request or compared with its result
+    fn values(&self, config: &Config) -> Values {
+        match *self {
+            Request::Insert(pk)
+            | Request::Select(pk)
+            | Request::Update(pk)
+            | Request::LwtInsert(pk)
+            | Request::LwtUpdate(pk) => Values::Row(config.schema.row(pk)),
+            Request::InsertWide { pk, ck } => {
+                Values::Row(config.schema.row(pk * config.rows_per_partition + ck))
+            }
+            Request::Batch(key) => Values::Batch(batch_inserts(config, key)),
+            // Values of a point are generated from its timestamp
+            Request::InsertPoint { age, .. } => {
+                let ts = now_ms() - age;
+                Values::Point {
+                    ts,
+                    row: config.schema.row(ts),
+                }
+            }
+            // Points inserted before midnight aren't read, the sensor has a new partition every day
+            Request::SelectPoints(_) => Values::Day(now_ms() / MS_PER_DAY),
+            Request::Delete(_)
+            | Request::SelectDeleted(_)
+            | Request::Scan(_)
+            | Request::CounterUpdate(_) => Values::None,
+        }
+    }
+
+    // Performs only the driver call, which is what the latency is measured of
+    async fn send<D: Driver>(
+        &self,
+        driver: &D,
+        config: &Config,
+        values: &Values,
+    ) -> Result<Response> {
+        let response = match (*self, values) {
+            (Request::Insert(pk), Values::Row(row)) => {
+                driver.insert(pk, row).await?;
+                Response::Done
+            }
+            (Request::Select(pk), _) | (Request::SelectDeleted(pk), _) => {
+                Response::Row(driver.select(pk).await?)
+            }
+            // Updated rows keep the same values, so that they can still be verified by selects
+            (Request::Update(pk), Values::Row(row)) => {
+                driver.update(pk, row).await?;
+                Response::Done
+            }
+            (Request::Delete(pk), _) => {
+                driver.delete(pk).await?;
+                Response::Done
+            }
+            (Request::InsertWide { pk, ck }, Values::Row(row)) => {
+                driver.insert_wide(pk, ck, row).await?;
+                Response::Done
+            }
+            (Request::Scan(pk), _) => Response::RowCount(driver.scan(pk).await?),
+            (Request::Batch(_), Values::Batch(inserts)) => {
+                driver.batch(inserts).await?;
+                Response::Done
+            }
+            (Request::CounterUpdate(pk), _) => {
+                driver.counter_update(pk, COUNTER_INCREMENT).await?;
+                Response::Done
+            }
+            (Request::InsertPoint { sensor, .. }, Values::Point { ts, row }) => {
+                driver
+                    .insert_point(sensor, ts / MS_PER_DAY, *ts, row)
+                    .await?;
+                Response::Done
+            }
+            (Request::SelectPoints(sensor), Values::Day(day)) => {
+                let points = driver
+                    .select_points(sensor, *day, config.points_per_read)
+                    .await?;
+                Response::Points(points)
+            }
+            // Not applied when the key was already inserted earlier in the run
+            (Request::LwtInsert(pk), Values::Row(row)) => {
+                Response::Applied(driver.lwt_insert(pk, row).await?)
+            }
+            // The condition holds as long as nothing else modified the row
+            (Request::LwtUpdate(pk), Values::Row(row)) => {
+                Response::Applied(driver.lwt_update(pk, row, &row[0]).await?)
+            }
+            _ => unreachable!("Values don't match the request"),
+        };
+
+        Ok(response)
+    }
+
+    // Checks the response against the values, conditional requests return whether they were applied.
+    // A mismatch is an error rather than a panic, e.g. a read with a low consistency can see a stale replica.
+    fn verify(&self, config: &Config, values: &Values, response: Response) -> Result<Option<bool>> {
+        match (*self, values, response) {
+            (Request::Select(pk), Values::Row(expected), Response::Row(row)) => {
+                let row = row.ok_or_else(|| anyhow!("No row with pk = {}", pk))?;
+                if row != *expected {
+                    return Err(anyhow!(
+                        "Row with pk = {} is {:?}, expected {:?}",
+                        pk,
+                        row,
+                        expected
+                    ));
+                }
+            }
+            (Request::SelectDeleted(pk), _, Response::Row(Some(_))) => {
+                return Err(anyhow!("Row with pk = {} wasn't deleted", pk));
+            }
+            (Request::Scan(pk), _, Response::RowCount(rows))
+                if rows != config.rows_per_partition =>
+            {
+                return Err(anyhow!(
+                    "Partition pk = {} has {} rows, expected {}",
+                    pk,
+                    rows,
+                    config.rows_per_partition
+                ));
+            }
+            (Request::SelectPoints(sensor), _, Response::Points(points)) => {
+                if points.len() > config.points_per_read as usize {
+                    return Err(anyhow!(
+                        "Sensor {} returned {} points, at most {} were requested",
+                        sensor,
+                        points.len(),
+                        config.points_per_read
+                    ));
+                }
+                for (index, (ts, values)) in points.iter().enumerate() {
+                    if index > 0 && *ts >= points[index - 1].0 {
+                        return Err(anyhow!(
+                            "Points of sensor {} aren't sorted newest first",
+                            sensor
+                        ));
+                    }
+                    if *values != config.schema.row(*ts) {
+                        return Err(anyhow!(
+                            "Point of sensor {} at {} has wrong values {:?}",
+                            sensor,
+                            ts,
+                            values
+                        ));
+                    }
+                }
+            }
+            (_, _, Response::Applied(applied)) => return Ok(Some(applied)),
+            _ => {}
+        }
+
+        Ok(None)
+    }
+}
+
+// Values written by a request or compared with its result, generated before the request is timed
+enum Values {
+    None,
+    Row(Vec<Value>),
+    Batch(Vec<BatchInsert>),
+    Point { ts: i64, row: Vec<Value> },
+    // Day of the partition read by a select of points
+    Day(i64),
+}
+
+// Result of the driver call of a request
+enum Response {
+    Done,
+    Row(Option<Vec<Value>>),
+    RowCount(i64),
+    Points(Vec<(i64, Vec<Value>)>),
+    Applied(bool),
+}
+
+// Batch number `key` inserts rows `0..n` of the partition `key` of `widetab`
+// or rows `key * n..(key + 1) * n` of the main table, where n = `config.statements_per_batch`
+fn batch_inserts(config: &Config, key: i64) -> Vec<BatchInsert> {
+    let n = config.statements_per_batch;
+
+    (0..n)
+        .map(|i| {
+            if config.same_partition_batches {
+                BatchInsert {
+                    pk: key,
+                    ck: Some(i),
+                    values: config.schema.row(key * n + i),
+                }
+            } else {
+                BatchInsert {
+                    pk: key * n + i,
+                    ck: None,
+                    values: config.schema.row(key * n + i),
+                }
+            }
+        })
+        .collect()
+}
+
+// Adds the error to the shared count, the first error of a phase is printed
+fn count_error(kind: ErrorKind, error: &anyhow::Error, error_count: &AtomicU64) {
+    if error_count.fetch_add(1, Ordering::Relaxed) == 0 {
+        eprintln!("Request failed ({}): {:#}", kind, error);
+    }
+}
+
+fn too_many_errors(config: &Config, error_count: &AtomicU64) -> bool {
+    match config.max_errors {
+        Some(max_errors) => error_count.load(Ordering::Relaxed) > max_errors,
+        None => false,
+    }
+}
+
+// Inserts all rows, which are selected, updated, deleted or scanned during the benchmark
+pub async fn prepare_existing_rows<D: Driver>(driver: &Arc<D>, config: &Arc<Config>) -> Result<()> {
+    println!("Preparing the benchmark (inserting rows)...");
+
+    let tasks = match config.workload {
+        Workload::Scans => config.tasks * config.rows_per_partition,
+        Workload::Timeseries => config.sensors * config.points_per_read as i64,
+        _ => config.tasks,
+    };
+
+    let phase = Phase {
+        workload: config.workload,
+        prefill: true,
+        concurrency: std::cmp::max(1024, config.concurrency),
+        tasks,
+        key_offset: 0,
+        // Every key has to be inserted
+        distribution: KeyDistribution::Sequential,
+        rate: None,
+        duration: None,
+    };
+    // The benchmark would report errors or wrong results for rows which are missing
+    let stats: Stats = run_tasks(driver, config, phase).await?;
+    if stats.errors() > 0 {
+        return Err(anyhow!(
+            "{} of {} inserts preparing the benchmark failed",
+            stats.errors(),
+            tasks
+        ));
+    }
+
+    Ok(())
+}
+
+// Reads all counters and checks that their total matches the successful increments of `stats`.
+// Counter updates aren't idempotent, a failed increment might have been applied or not,
+// so each failed increment widens the range of accepted totals.
+pub async fn verify_counters<D: Driver>(
+    driver: &Arc<D>,
+    config: &Arc<Config>,
+    stats: &Stats,
+) -> Result<()> {
+    println!("\nVerifying counters...");
+
+    let increments = stats.operation(Operation::CounterUpdate);
+    let expected_min = increments.latency.len() as i64 * COUNTER_INCREMENT;
+    let expected_max = expected_min + increments.errors.total() as i64 * COUNTER_INCREMENT;
+
+    let workers = std::cmp::min(config.concurrency, config.counter_keys);
+    let mut handles = Vec::with_capacity(workers.try_into().unwrap());
+
+    for worker in 0..workers {
+        let driver = driver.clone();
+        let config = config.clone();
+
+        handles.push(tokio::spawn(async move {
+            let mut total: i64 = 0;
+            for pk in (worker..config.counter_keys).step_by(workers as usize) {
+                total += driver.counter_select(pk).await?.unwrap_or(0);
+            }
+            Ok::<i64, anyhow::Error>(total)
+        }));
+    }
+
+    let mut total: i64 = 0;
+    for handle in handles {
+        total += handle.await??;
+    }
+
+    if total < expected_min || total > expected_max {
+        return Err(anyhow!(
+            "Counters total is {}, expected {} (at most {} including failed increments)",
+            total,
+            expected_min,
+            expected_max
+        ));
+    }
+
+    println!("Counters total: {} (expected {})", total, expected_min);
+    if total != expected_min {
+        println!(
+            "{} of the failed increments were applied anyway",
+            (total - expected_min) / COUNTER_INCREMENT
+        );
+    }
+
+    Ok(())
+}
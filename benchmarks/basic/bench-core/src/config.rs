@@ -0,0 +1,1347 @@
+use crate::schema::{ColumnType, Replication, Schema};
+use anyhow::{anyhow, Result};
+use getopts::Options;
+use serde::{Serialize, Serializer};
+use std::convert::TryInto;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Workload {
+    Inserts,
+    Selects,
+    Mixed,
+    Updates,
+    Deletes,
+    Scans,
+    Batches,
+    #[serde(rename = "lwt-inserts")]
+    LwtInserts,
+    #[serde(rename = "lwt-updates")]
+    LwtUpdates,
+    Counters,
+    Timeseries,
+}
+
+// Distribution of keys operated on by the tasks, all keys are from `0..tasks`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyDistribution {
+    // Task number `i` operates on key `i % tasks`
+    Sequential,
+    Uniform,
+    // Key `k` is chosen with probability proportional to `1 / (k + 1)^skew`
+    Zipf { skew: f64 },
+    // Centered in the middle of the key range, `stddev` is a fraction of the key range
+    Gaussian { stddev: f64 },
+}
+
+// Written the same way as the --distribution option, so that it's a single CSV column
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyDistribution::Sequential => write!(f, "sequential"),
+            KeyDistribution::Uniform => write!(f, "uniform"),
+            KeyDistribution::Zipf { skew } => write!(f, "zipf:{}", skew),
+            KeyDistribution::Gaussian { stddev } => write!(f, "gaussian:{}", stddev),
+        }
+    }
+}
+
+impl Serialize for KeyDistribution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchType {
+    Logged,
+    Unlogged,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Consistency {
+    Any,
+    One,
+    Two,
+    Three,
+    Quorum,
+    All,
+    LocalQuorum,
+    EachQuorum,
+    LocalOne,
+}
+
+// Consistency of the Paxos phase of lightweight transactions
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SerialConsistency {
+    Serial,
+    LocalSerial,
+}
+
+// Policy choosing the node a request is sent to, token awareness is a separate toggle
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LoadBalancing {
+    RoundRobin,
+    // Round robin over the nodes of the local datacenter
+    DcAware,
+}
+
+// Load balancing a driver actually uses, including its defaults for options that weren't given
+#[derive(Clone, Debug, Serialize)]
+pub struct LoadBalancingSettings {
+    pub policy: LoadBalancing,
+    // None when the driver infers the local datacenter
+    pub local_dc: Option<String>,
+    pub token_aware: bool,
+    pub shard_aware: bool,
+}
+
+// Compression of CQL frames
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Lz4,
+    Snappy,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Lz4 => write!(f, "lz4"),
+            Compression::Snappy => write!(f, "snappy"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+// Shown as asterisks when the configuration is printed or reported
+#[derive(Clone)]
+pub struct Password(String);
+
+impl Password {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"***\"")
+    }
+}
+
+impl Serialize for Password {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str("***")
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Config {
+    pub node_addresses: Vec<String>,
+    pub workload: Workload,
+    // Probability that a task of the mixed or timeseries workload is a select,
+    // None means insert and then select every key
+    pub read_ratio: Option<f64>,
+    pub tasks: i64,
+    pub distribution: KeyDistribution,
+    pub seed: Option<u64>,
+    pub concurrency: i64,
+    // None means the default consistency of the driver
+    pub consistency: Option<Consistency>,
+    pub serial_consistency: Option<SerialConsistency>,
+    // None means the default of the driver
+    pub load_balancing: Option<LoadBalancing>,
+    // Local datacenter of the dc-aware policy
+    pub local_dc: Option<String>,
+    pub token_aware: Option<bool>,
+    pub shard_aware: Option<bool>,
+    // Connect to the nodes over TLS
+    pub tls: bool,
+    // CA certificate the certificates of the nodes are verified with, they aren't verified without it
+    pub ca_cert: Option<String>,
+    // Certificate and private key presented to nodes which require client authentication
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+    // Credentials for clusters with PasswordAuthenticator
+    pub user: Option<String>,
+    pub password: Option<Password>,
+    pub compression: Compression,
+    // Size of the connection pool, at most one of them is set, None means the default of the driver
+    pub connections_per_host: Option<u32>,
+    pub connections_per_shard: Option<u32>,
+    pub batch_size: i64,
+    pub dont_prepare: bool,
+    // Select every row after deleting it in the deletes workload and check that it's gone
+    pub verify_deletes: bool,
+    pub schema: Schema,
+    // Rows in each partition of `widetab` read by the scans workload
+    pub rows_per_partition: i64,
+    pub page_size: i32,
+    // Inserts in a single CQL BATCH of the batches workload
+    pub statements_per_batch: i64,
+    pub batch_type: BatchType,
+    // All inserts of a batch go to one partition of `widetab` instead of different rows of the main table
+    pub same_partition_batches: bool,
+    // Number of counters in `countertab` incremented by the counters workload
+    pub counter_keys: i64,
+    // Read all counters after the counters workload and check their total
+    pub verify_counters: bool,
+    // Sensors of the timeseries workload, each of them has a partition in `timeseries` for every day
+    pub sensors: i64,
+    // Latest points of a sensor read by a select of the timeseries workload
+    pub points_per_read: i32,
+    // Time to live of inserted points in seconds
+    pub ttl: i32,
+    pub rate: Option<f64>,
+    #[serde(serialize_with = "serialize_secs")]
+    pub duration: Option<Duration>,
+    // At most one of the warmup options is set
+    pub warmup_tasks: Option<i64>,
+    #[serde(serialize_with = "serialize_secs")]
+    pub warmup_duration: Option<Duration>,
+    pub max_errors: Option<u64>,
+    pub output_format: OutputFormat,
+    pub output_file: Option<String>,
+}
+
+impl Config {
+    pub fn read(default_nodes: &str) -> Result<Option<Config>> {
+        let args: Vec<String> = std::env::args().collect();
+        Config::parse(&args[1..], default_nodes)
+    }
+
+    // Parses the arguments without the program name
+    fn parse(args: &[String], default_nodes: &str) -> Result<Option<Config>> {
+        let mut opts = Options::new();
+
+        opts.optflag("h", "help", "Print usage information");
+
+        opts.optopt(
+            "n",
+            "nodes",
+            &format!(
+                "Addresses of database nodes to connect to separated by a comma
+            (default: '{}')",
+                default_nodes
+            ),
+            "ADDRESS",
+        );
+        opts.optopt(
+            "w",
+            "workload",
+            "Type of work to perform (inserts, selects, mixed, updates, deletes, scans, batches, lwt-inserts, lwt-updates, counters, timeseries) (default: mixed)",
+            "WORKLOAD",
+        );
+        opts.optopt(
+            "",
+            "read-ratio",
+            "Make every task of the mixed or timeseries workload a single select with probability READ_RATIO (e.g. 0.9)
+            and a single insert otherwise. Selects read random keys, which are inserted before the benchmark
+            (default: every task inserts a row and then selects it)",
+            "READ_RATIO",
+        );
+        opts.optopt("t", "tasks", "Total number of tasks (requests) to perform the during benchmark
+                                In case of mixed workload there will be tasks inserts and tasks selects
+                                (default: 1 000 000)", "TASKS");
+        opts.optopt(
+            "",
+            "distribution",
+            "Distribution of keys in the range 0..TASKS: sequential, uniform, zipf[:SKEW], gaussian[:STDDEV]
+            SKEW is the exponent of the zipf distribution (default: 1.0)
+            STDDEV is the standard deviation of the gaussian distribution as a fraction of the key range (default: 0.1)
+            (default: sequential)",
+            "DISTRIBUTION",
+        );
+        opts.optopt(
+            "",
+            "seed",
+            "Seed of the random number generators, makes the sequence of keys of every worker deterministic
+            (default: random)",
+            "SEED",
+        );
+        opts.optopt(
+            "c",
+            "concurrency",
+            "Maximum number of requests performed at once
+            (default: 1024)",
+            "CONCURRENCY",
+        );
+        opts.optopt(
+            "",
+            "consistency",
+            "Consistency level of all statements (ANY, ONE, TWO, THREE, QUORUM, ALL, LOCAL_QUORUM, EACH_QUORUM, LOCAL_ONE)
+            (default: the default of the driver)",
+            "CONSISTENCY",
+        );
+        opts.optopt(
+            "",
+            "serial-consistency",
+            "Serial consistency level of lightweight transactions (SERIAL, LOCAL_SERIAL)
+            (default: the default of the driver)",
+            "CONSISTENCY",
+        );
+
+        opts.optopt(
+            "",
+            "load-balancing",
+            "Load balancing policy: round-robin or dc-aware, which requires --local-dc
+            (default: the default of the driver)",
+            "POLICY",
+        );
+        opts.optopt(
+            "",
+            "local-dc",
+            "Local datacenter of the dc-aware load balancing policy",
+            "DC",
+        );
+        opts.optopt(
+            "",
+            "token-aware",
+            "Send requests to replicas of the partition: on or off
+            (default: the default of the driver)",
+            "on|off",
+        );
+        opts.optopt(
+            "",
+            "shard-aware",
+            "Send requests to the shard which owns the partition: on or off.
+            scylla-rust-driver can't turn it off, other drivers can't turn it on
+            (default: the default of the driver)",
+            "on|off",
+        );
+
+        opts.optflag("", "tls", "Connect to the nodes over TLS");
+        opts.optopt(
+            "",
+            "ca-cert",
+            "PEM file with the CA certificate which the certificates of the nodes are verified with
+            (default: don't verify the certificates of the nodes)",
+            "PATH",
+        );
+        opts.optopt(
+            "",
+            "client-cert",
+            "PEM file with the certificate presented to the nodes, requires --client-key",
+            "PATH",
+        );
+        opts.optopt(
+            "",
+            "client-key",
+            "PEM file with the private key of the client certificate",
+            "PATH",
+        );
+
+        opts.optopt(
+            "u",
+            "user",
+            "Name of the user to authenticate as, requires --password
+            (default: the BENCH_USER environment variable, no authentication if it's not set)",
+            "USER",
+        );
+        opts.optopt(
+            "p",
+            "password",
+            "Password of the user, the BENCH_PASSWORD environment variable keeps it out of the shell history
+            (default: the BENCH_PASSWORD environment variable)",
+            "PASSWORD",
+        );
+
+        opts.optopt(
+            "",
+            "compression",
+            "Compression of CQL frames: none, lz4 or snappy
+            (default: none)",
+            "COMPRESSION",
+        );
+        opts.optopt(
+            "",
+            "connections-per-host",
+            "Number of connections to each node
+            (default: the default of the driver)",
+            "CONNECTIONS",
+        );
+        opts.optopt(
+            "",
+            "connections-per-shard",
+            "Number of connections to each shard of a node, only for shard aware drivers
+            (default: the default of the driver)",
+            "CONNECTIONS",
+        );
+
+        opts.optflag(
+            "d",
+            "dont-prepare",
+            "Don't create tables and insert into them before the benchmark",
+        );
+
+        opts.optflag(
+            "",
+            "verify-deletes",
+            "In the deletes workload select every deleted row and check that it doesn't exist anymore",
+        );
+
+        opts.optopt(
+            "",
+            "rows-per-partition",
+            "Number of rows in each partition read by the scans workload
+            (default: 100)",
+            "ROWS",
+        );
+        opts.optopt(
+            "",
+            "page-size",
+            "Number of rows fetched in a single page by the scans workload
+            (default: 5000)",
+            "ROWS",
+        );
+
+        opts.optopt(
+            "",
+            "statements-per-batch",
+            "Number of inserts in a single BATCH statement of the batches workload
+            (default: 10)",
+            "STATEMENTS",
+        );
+        opts.optopt(
+            "",
+            "batch-type",
+            "Type of BATCH statements of the batches workload (logged, unlogged)
+            (default: unlogged)",
+            "TYPE",
+        );
+        opts.optflag(
+            "",
+            "same-partition-batches",
+            "Insert rows of a single partition in each batch, instead of rows of different partitions",
+        );
+
+        opts.optopt(
+            "",
+            "counter-keys",
+            "Number of counters incremented by the counters workload
+            (default: tasks)",
+            "KEYS",
+        );
+        opts.optflag(
+            "",
+            "verify-counters",
+            "After the counters workload read all counters and check that no increment was lost or applied twice",
+        );
+
+        opts.optopt(
+            "",
+            "sensors",
+            "Number of sensors which the timeseries workload inserts points of
+            (default: 1000)",
+            "SENSORS",
+        );
+        opts.optopt(
+            "",
+            "points-per-read",
+            "Number of the latest points of a sensor read by a select of the timeseries workload
+            (default: 100)",
+            "POINTS",
+        );
+        opts.optopt(
+            "",
+            "ttl",
+            "Time to live of points inserted by the timeseries workload (e.g. 3600s, 24h)
+            (default: 24h)",
+            "TTL",
+        );
+
+        opts.optopt(
+            "",
+            "keyspace",
+            "Name of the keyspace, which is dropped and recreated before the benchmark
+            (default: benchks)",
+            "NAME",
+        );
+        opts.optopt(
+            "",
+            "table",
+            "Name of the main table of the keyspace
+            (default: benchtab)",
+            "NAME",
+        );
+        opts.optopt(
+            "",
+            "replication-strategy",
+            "Replication strategy of the keyspace (simple, network-topology)
+            (default: simple)",
+            "STRATEGY",
+        );
+        opts.optopt(
+            "",
+            "replication-factor",
+            "Replication factor of the simple strategy (e.g. 3)
+            or of every datacenter of the network-topology strategy (e.g. dc1:3,dc2:3)
+            (default: 1)",
+            "FACTOR",
+        );
+
+        opts.optopt(
+            "",
+            "columns",
+            "Number of value columns in the table, besides the bigint primary key
+            (default: 2)",
+            "COLUMNS",
+        );
+        opts.optopt(
+            "",
+            "column-type",
+            "Types of the value columns separated by a comma (bigint, text, blob, uuid, timestamp, list, set, map, udt)
+            The list is repeated if there are more columns than types
+            (default: bigint)",
+            "TYPES",
+        );
+        opts.optopt(
+            "",
+            "value-size",
+            "Size of each text and blob value (e.g. 100, 1KiB, 10KiB)
+            (default: 16B)",
+            "SIZE",
+        );
+        opts.optopt(
+            "",
+            "collection-size",
+            "Number of elements of each list, set and map value
+            (default: 10)",
+            "ELEMENTS",
+        );
+
+        opts.optopt(
+            "r",
+            "rate",
+            "Start tasks at a fixed rate of RATE tasks per second, regardless of responses (open-loop load)
+            Latency is measured from the scheduled start of a request
+            (default: start the next task as soon as the previous one finishes)",
+            "RATE",
+        );
+
+        opts.optopt(
+            "",
+            "duration",
+            "Run the benchmark for the given time (e.g. 60s, 500ms, 10m) instead of performing a fixed number of tasks
+            Keys wrap around after TASKS keys, so TASKS is the size of the key space
+            (default: run until TASKS tasks are done)",
+            "DURATION",
+        );
+
+        opts.optopt(
+            "",
+            "warmup",
+            "Before the benchmark run the same workload for the given number of tasks (e.g. 100000)
+            or the given time (e.g. 10s) and discard its results",
+            "TASKS|DURATION",
+        );
+
+        opts.optopt(
+            "",
+            "max-errors",
+            "Abort the benchmark when more than MAX_ERRORS requests have failed
+            Failed requests are counted by error type and reported at the end
+            (default: never abort because of errors)",
+            "MAX_ERRORS",
+        );
+
+        opts.optopt(
+            "",
+            "output-format",
+            "Format of the result record (text, json, csv)
+            json and csv records contain the configuration, time, throughput and latencies
+            (default: text)",
+            "FORMAT",
+        );
+        opts.optopt(
+            "",
+            "output-file",
+            "Append the result record to this file instead of printing it to stdout",
+            "PATH",
+        );
+
+        let parsed = opts.parse(args)?;
+
+        if parsed.opt_present("help") {
+            println!("{}", opts.usage("Benchmark configuration"));
+            return Ok(None);
+        }
+
+        let addresses: String = parsed.opt_get_default("nodes", default_nodes.to_string())?;
+
+        let node_addresses: Vec<String> = addresses.split(',').map(|s| s.to_string()).collect();
+
+        let workload_str: String = parsed.opt_get_default("workload", "inserts".to_string())?;
+        let workload: Workload = match workload_str.as_str() {
+            "inserts" => Workload::Inserts,
+            "selects" => Workload::Selects,
+            "mixed" => Workload::Mixed,
+            "updates" => Workload::Updates,
+            "deletes" => Workload::Deletes,
+            "scans" => Workload::Scans,
+            "batches" => Workload::Batches,
+            "lwt-inserts" => Workload::LwtInserts,
+            "lwt-updates" => Workload::LwtUpdates,
+            "counters" => Workload::Counters,
+            "timeseries" => Workload::Timeseries,
+            other => {
+                return Err(anyhow!(
+                    "Invalid workload type: '{}'. Allowed values: inserts, selects, mixed, updates, deletes, scans, batches, lwt-inserts, lwt-updates, counters, timeseries",
+                    other
+                ))
+            }
+        };
+
+        let read_ratio: Option<f64> = parsed.opt_get("read-ratio")?;
+        if let Some(read_ratio) = read_ratio {
+            if !(0.0..=1.0).contains(&read_ratio) {
+                return Err(anyhow!(
+                    "Invalid read ratio: {}. Read ratio must be between 0 and 1",
+                    read_ratio
+                ));
+            }
+
+            if workload != Workload::Mixed && workload != Workload::Timeseries {
+                return Err(anyhow!(
+                    "--read-ratio can only be used with the mixed and timeseries workloads"
+                ));
+            }
+        }
+
+        let tasks: i64 = parsed.opt_get_default("tasks", 1_000_000)?;
+        // Keys are taken from `0..tasks`, so there has to be at least one even with --duration
+        if tasks <= 0 {
+            return Err(anyhow!("The number of tasks has to be positive"));
+        }
+        let distribution: KeyDistribution = match parsed.opt_str("distribution") {
+            Some(distribution_str) => parse_distribution(&distribution_str)?,
+            None => KeyDistribution::Sequential,
+        };
+        let seed: Option<u64> = parsed.opt_get("seed")?;
+
+        let concurrency: i64 = parsed.opt_get_default("concurrency", 1024)?;
+        if concurrency <= 0 {
+            return Err(anyhow!("The concurrency has to be positive"));
+        }
+
+        let consistency: Option<Consistency> = match parsed.opt_str("consistency") {
+            Some(consistency_str) => Some(parse_consistency(&consistency_str)?),
+            None => None,
+        };
+        let serial_consistency: Option<SerialConsistency> =
+            match parsed.opt_str("serial-consistency") {
+                Some(consistency_str) => Some(parse_serial_consistency(&consistency_str)?),
+                None => None,
+            };
+
+        let load_balancing: Option<LoadBalancing> = match parsed.opt_str("load-balancing") {
+            Some(policy_str) => Some(match policy_str.as_str() {
+                "round-robin" => LoadBalancing::RoundRobin,
+                "dc-aware" => LoadBalancing::DcAware,
+                other => {
+                    return Err(anyhow!(
+                    "Invalid load balancing policy: '{}'. Allowed values: round-robin, dc-aware",
+                    other
+                ))
+                }
+            }),
+            None => None,
+        };
+        let local_dc: Option<String> = parsed.opt_str("local-dc");
+        if (load_balancing == Some(LoadBalancing::DcAware)) != local_dc.is_some() {
+            return Err(anyhow!(
+                "--local-dc has to be used exactly with the dc-aware load balancing policy"
+            ));
+        }
+        let token_aware: Option<bool> = parse_toggle(&parsed, "token-aware")?;
+        let shard_aware: Option<bool> = parse_toggle(&parsed, "shard-aware")?;
+
+        let tls: bool = parsed.opt_present("tls");
+        let ca_cert: Option<String> = parsed.opt_str("ca-cert");
+        let client_cert: Option<String> = parsed.opt_str("client-cert");
+        let client_key: Option<String> = parsed.opt_str("client-key");
+        if !tls && (ca_cert.is_some() || client_cert.is_some() || client_key.is_some()) {
+            return Err(anyhow!(
+                "--ca-cert, --client-cert and --client-key can only be used with --tls"
+            ));
+        }
+        if client_cert.is_some() != client_key.is_some() {
+            return Err(anyhow!(
+                "--client-cert and --client-key have to be used together"
+            ));
+        }
+
+        let user: Option<String> = parsed
+            .opt_str("user")
+            .or_else(|| std::env::var("BENCH_USER").ok());
+        let password: Option<Password> = parsed
+            .opt_str("password")
+            .or_else(|| std::env::var("BENCH_PASSWORD").ok())
+            .map(Password);
+        if user.is_some() != password.is_some() {
+            return Err(anyhow!(
+                "Both a user and a password are needed for authentication"
+            ));
+        }
+
+        let compression_str: String = parsed.opt_get_default("compression", "none".to_string())?;
+        let compression: Compression = match compression_str.as_str() {
+            "none" => Compression::None,
+            "lz4" => Compression::Lz4,
+            "snappy" => Compression::Snappy,
+            other => {
+                return Err(anyhow!(
+                    "Invalid compression: '{}'. Allowed values: none, lz4, snappy",
+                    other
+                ))
+            }
+        };
+
+        let connections_per_host: Option<u32> = parsed.opt_get("connections-per-host")?;
+        let connections_per_shard: Option<u32> = parsed.opt_get("connections-per-shard")?;
+        if connections_per_host.is_some() && connections_per_shard.is_some() {
+            return Err(anyhow!(
+                "--connections-per-host and --connections-per-shard can't be used together"
+            ));
+        }
+        if connections_per_host == Some(0) || connections_per_shard == Some(0) {
+            return Err(anyhow!("The number of connections has to be positive"));
+        }
+
+        let dont_prepare: bool = parsed.opt_present("dont-prepare");
+        let verify_deletes: bool = parsed.opt_present("verify-deletes");
+        if verify_deletes && workload != Workload::Deletes {
+            return Err(anyhow!(
+                "--verify-deletes can only be used with the deletes workload"
+            ));
+        }
+
+        let column_count: usize = parsed.opt_get_default("columns", 2)?;
+        if column_count == 0 {
+            return Err(anyhow!("The table needs at least one value column"));
+        }
+
+        let column_types: Vec<ColumnType> = parsed
+            .opt_get_default("column-type", "bigint".to_string())?
+            .split(',')
+            .map(parse_column_type)
+            .collect::<Result<_>>()?;
+
+        let value_size: usize = match parsed.opt_str("value-size") {
+            Some(size_str) => parse_size(&size_str)?,
+            None => 16,
+        };
+
+        // Empty collections are returned as nulls, so they couldn't be verified
+        let collection_size: usize = parsed.opt_get_default("collection-size", 10)?;
+        if collection_size == 0 {
+            return Err(anyhow!("Collection size must be positive"));
+        }
+
+        let keyspace: String = parsed.opt_get_default("keyspace", "benchks".to_string())?;
+        validate_name(&keyspace)?;
+        let table: String = parsed.opt_get_default("table", "benchtab".to_string())?;
+        validate_name(&table)?;
+        if ["widetab", "countertab", "timeseries"].contains(&table.as_str()) {
+            return Err(anyhow!(
+                "Table name '{}' is used by another table of the benchmark",
+                table
+            ));
+        }
+
+        let replication_strategy: String =
+            parsed.opt_get_default("replication-strategy", "simple".to_string())?;
+        let replication_factor: String =
+            parsed.opt_get_default("replication-factor", "1".to_string())?;
+        let replication = parse_replication(&replication_strategy, &replication_factor)?;
+
+        let schema = Schema {
+            keyspace,
+            replication,
+            table,
+            columns: column_types
+                .iter()
+                .cycle()
+                .take(column_count)
+                .copied()
+                .collect(),
+            value_size,
+            collection_size,
+        };
+
+        let rows_per_partition: i64 = parsed.opt_get_default("rows-per-partition", 100)?;
+        let page_size: i32 = parsed.opt_get_default("page-size", 5000)?;
+        if rows_per_partition <= 0 || page_size <= 0 {
+            return Err(anyhow!("Rows per partition and page size must be positive"));
+        }
+
+        let statements_per_batch: i64 = parsed.opt_get_default("statements-per-batch", 10)?;
+        if statements_per_batch <= 0 {
+            return Err(anyhow!("Statements per batch must be positive"));
+        }
+
+        let batch_type_str: String =
+            parsed.opt_get_default("batch-type", "unlogged".to_string())?;
+        let batch_type: BatchType = match batch_type_str.as_str() {
+            "logged" => BatchType::Logged,
+            "unlogged" => BatchType::Unlogged,
+            other => {
+                return Err(anyhow!(
+                    "Invalid batch type: '{}'. Allowed values: logged, unlogged",
+                    other
+                ))
+            }
+        };
+
+        let same_partition_batches: bool = parsed.opt_present("same-partition-batches");
+
+        let counter_keys: i64 = parsed.opt_get_default("counter-keys", tasks)?;
+        if counter_keys <= 0 {
+            return Err(anyhow!("Number of counter keys must be positive"));
+        }
+
+        let verify_counters: bool = parsed.opt_present("verify-counters");
+        if verify_counters && workload != Workload::Counters {
+            return Err(anyhow!(
+                "--verify-counters can only be used with the counters workload"
+            ));
+        }
+        // Counters left by earlier runs would be included in the totals
+        if verify_counters && dont_prepare {
+            return Err(anyhow!(
+                "--verify-counters can't be used with --dont-prepare"
+            ));
+        }
+
+        let sensors: i64 = parsed.opt_get_default("sensors", 1000)?;
+        let points_per_read: i32 = parsed.opt_get_default("points-per-read", 100)?;
+        if sensors <= 0 || points_per_read <= 0 {
+            return Err(anyhow!(
+                "Number of sensors and points per read must be positive"
+            ));
+        }
+
+        let ttl: i32 = match parsed.opt_str("ttl") {
+            Some(ttl_str) => parse_duration(&ttl_str)?.as_secs().try_into()?,
+            None => 24 * 3600,
+        };
+        if ttl <= 0 {
+            return Err(anyhow!("TTL must be at least one second"));
+        }
+
+        let rate: Option<f64> = parsed.opt_get("rate")?;
+        if let Some(rate) = rate {
+            if !rate.is_finite() || rate <= 0.0 {
+                return Err(anyhow!("Invalid rate: {}. Rate must be positive", rate));
+            }
+        }
+
+        let duration: Option<Duration> = match parsed.opt_str("duration") {
+            Some(duration_str) => Some(parse_duration(&duration_str)?),
+            None => None,
+        };
+
+        // A plain number is a number of tasks, a number with a unit is a duration
+        let (warmup_tasks, warmup_duration) = match parsed.opt_str("warmup") {
+            Some(warmup_str) => match warmup_str.parse::<i64>() {
+                Ok(warmup_tasks) => (Some(warmup_tasks), None),
+                Err(_) => (None, Some(parse_duration(&warmup_str)?)),
+            },
+            None => (None, None),
+        };
+        // The warmup would leave only already deleted rows or already inserted keys to the benchmark
+        if (warmup_tasks.is_some() || warmup_duration.is_some())
+            && (workload == Workload::Deletes || workload == Workload::LwtInserts)
+        {
+            return Err(anyhow!(
+                "--warmup can't be used with the deletes and lwt-inserts workloads"
+            ));
+        }
+
+        let max_errors: Option<u64> = parsed.opt_get("max-errors")?;
+
+        let output_format_str: String =
+            parsed.opt_get_default("output-format", "text".to_string())?;
+        let output_format: OutputFormat = match output_format_str.as_str() {
+            "text" => OutputFormat::Text,
+            "json" => OutputFormat::Json,
+            "csv" => OutputFormat::Csv,
+            other => {
+                return Err(anyhow!(
+                    "Invalid output format: '{}'. Allowed values: text, json, csv",
+                    other
+                ))
+            }
+        };
+
+        let output_file: Option<String> = parsed.opt_str("output-file");
+
+        let mut batch_size = 256;
+
+        if tasks / batch_size < concurrency {
+            batch_size = std::cmp::max(1, tasks / concurrency);
+        }
+
+        // With a fixed rate every task has to take the next scheduled request,
+        // otherwise requests from a big batch would wait for each other
+        if rate.is_some() {
+            batch_size = 1;
+        }
+
+        Ok(Some(Config {
+            node_addresses,
+            workload,
+            read_ratio,
+            tasks,
+            distribution,
+            seed,
+            concurrency,
+            consistency,
+            serial_consistency,
+            load_balancing,
+            local_dc,
+            token_aware,
+            shard_aware,
+            tls,
+            ca_cert,
+            client_cert,
+            client_key,
+            user,
+            password,
+            compression,
+            connections_per_host,
+            connections_per_shard,
+            batch_size,
+            dont_prepare,
+            verify_deletes,
+            schema,
+            rows_per_partition,
+            page_size,
+            statements_per_batch,
+            batch_type,
+            same_partition_batches,
+            counter_keys,
+            verify_counters,
+            sensors,
+            points_per_read,
+            ttl,
+            rate,
+            duration,
+            warmup_tasks,
+            warmup_duration,
+            max_errors,
+            output_format,
+            output_file,
+        }))
+    }
+
+    // Whether the benchmark operates on rows, which have to be inserted before it starts.
+    // The scans workload reads `widetab`, the timeseries workload `timeseries`,
+    // all other workloads use the main table.
+    pub fn needs_existing_rows(&self) -> bool {
+        match self.workload {
+            Workload::Selects
+            | Workload::Updates
+            | Workload::Deletes
+            | Workload::Scans
+            | Workload::LwtUpdates => true,
+            Workload::Mixed | Workload::Timeseries => self.read_ratio.is_some(),
+            Workload::Inserts | Workload::Batches | Workload::LwtInserts | Workload::Counters => {
+                false
+            }
+        }
+    }
+
+    // Number of distinct keys operated on, tasks take keys from `0..key_count()`
+    pub fn key_count(&self) -> i64 {
+        match self.workload {
+            Workload::Counters => self.counter_keys,
+            Workload::Timeseries => self.sensors,
+            _ => self.tasks,
+        }
+    }
+}
+
+// Parses distributions like "uniform", "zipf:1.2" or "gaussian:0.05"
+fn parse_distribution(text: &str) -> Result<KeyDistribution> {
+    let (name, parameter) = match text.split_once(':') {
+        Some((name, parameter)) => (name, Some(parameter)),
+        None => (text, None),
+    };
+
+    let parameter: Option<f64> = match parameter {
+        Some(parameter) => Some(
+            parameter
+                .parse()
+                .map_err(|_| anyhow!("Invalid distribution parameter in '{}'", text))?,
+        ),
+        None => None,
+    };
+
+    let distribution = match (name, parameter) {
+        ("sequential", None) => KeyDistribution::Sequential,
+        ("uniform", None) => KeyDistribution::Uniform,
+        ("zipf", skew) => KeyDistribution::Zipf {
+            skew: skew.unwrap_or(1.0),
+        },
+        ("gaussian", stddev) => KeyDistribution::Gaussian {
+            stddev: stddev.unwrap_or(0.1),
+        },
+        ("sequential", Some(_)) | ("uniform", Some(_)) => {
+            return Err(anyhow!(
+                "Distribution '{}' doesn't take a parameter",
+                name
+            ))
+        }
+        _ => {
+            return Err(anyhow!(
+                "Invalid distribution: '{}'. Allowed values: sequential, uniform, zipf[:SKEW], gaussian[:STDDEV]",
+                text
+            ))
+        }
+    };
+
+    match distribution {
+        KeyDistribution::Zipf { skew } if !skew.is_finite() || skew < 0.0 => Err(anyhow!(
+            "Invalid zipf skew: {}. Skew can't be negative",
+            skew
+        )),
+        KeyDistribution::Gaussian { stddev } if !stddev.is_finite() || stddev <= 0.0 => {
+            Err(anyhow!(
+                "Invalid gaussian stddev: {}. Stddev must be positive",
+                stddev
+            ))
+        }
+        distribution => Ok(distribution),
+    }
+}
+
+// Value of an on/off option, None if it wasn't given
+fn parse_toggle(parsed: &getopts::Matches, name: &str) -> Result<Option<bool>> {
+    match parsed.opt_str(name).as_deref() {
+        Some("on") => Ok(Some(true)),
+        Some("off") => Ok(Some(false)),
+        Some(other) => Err(anyhow!(
+            "Invalid value of --{}: '{}'. Allowed values: on, off",
+            name,
+            other
+        )),
+        None => Ok(None),
+    }
+}
+
+// Consistency levels are accepted in any case, e.g. "local_quorum" or "LOCAL_QUORUM"
+fn parse_consistency(text: &str) -> Result<Consistency> {
+    match text.to_uppercase().as_str() {
+        "ANY" => Ok(Consistency::Any),
+        "ONE" => Ok(Consistency::One),
+        "TWO" => Ok(Consistency::Two),
+        "THREE" => Ok(Consistency::Three),
+        "QUORUM" => Ok(Consistency::Quorum),
+        "ALL" => Ok(Consistency::All),
+        "LOCAL_QUORUM" => Ok(Consistency::LocalQuorum),
+        "EACH_QUORUM" => Ok(Consistency::EachQuorum),
+        "LOCAL_ONE" => Ok(Consistency::LocalOne),
+        _ => Err(anyhow!(
+            "Invalid consistency: '{}'. Allowed values: ANY, ONE, TWO, THREE, QUORUM, ALL, LOCAL_QUORUM, EACH_QUORUM, LOCAL_ONE",
+            text
+        )),
+    }
+}
+
+fn parse_serial_consistency(text: &str) -> Result<SerialConsistency> {
+    match text.to_uppercase().as_str() {
+        "SERIAL" => Ok(SerialConsistency::Serial),
+        "LOCAL_SERIAL" => Ok(SerialConsistency::LocalSerial),
+        _ => Err(anyhow!(
+            "Invalid serial consistency: '{}'. Allowed values: SERIAL, LOCAL_SERIAL",
+            text
+        )),
+    }
+}
+
+// Names are used unquoted in the queries, so they are limited to lowercase identifiers
+fn validate_name(name: &str) -> Result<()> {
+    let valid = name.len() <= 48
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+
+    if !valid {
+        return Err(anyhow!(
+            "Invalid name: '{}'. Names must start with a lowercase letter and contain only lowercase letters, digits and underscores",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+// Parses the factor as a number for the simple strategy and as "dc1:3,dc2:3" for the network-topology strategy
+fn parse_replication(strategy: &str, factor: &str) -> Result<Replication> {
+    let parse_factor = |text: &str| -> Result<u32> {
+        match text.parse() {
+            Ok(factor) if factor > 0 => Ok(factor),
+            _ => Err(anyhow!(
+                "Invalid replication factor: '{}'. Replication factor must be positive",
+                text
+            )),
+        }
+    };
+
+    match strategy {
+        "simple" => Ok(Replication::Simple {
+            factor: parse_factor(factor)?,
+        }),
+        "network-topology" => {
+            let factors = factor
+                .split(',')
+                .map(|datacenter_factor| match datacenter_factor.split_once(':') {
+                    Some((datacenter, factor)) if !datacenter.is_empty() => {
+                        Ok((datacenter.to_string(), parse_factor(factor)?))
+                    }
+                    _ => Err(anyhow!(
+                        "Invalid datacenter replication factor: '{}'. The network-topology strategy needs factors like dc1:3,dc2:3",
+                        datacenter_factor
+                    )),
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(Replication::NetworkTopology { factors })
+        }
+        other => Err(anyhow!(
+            "Invalid replication strategy: '{}'. Allowed values: simple, network-topology",
+            other
+        )),
+    }
+}
+
+fn parse_column_type(text: &str) -> Result<ColumnType> {
+    match text {
+        "bigint" => Ok(ColumnType::Bigint),
+        "text" => Ok(ColumnType::Text),
+        "blob" => Ok(ColumnType::Blob),
+        "uuid" => Ok(ColumnType::Uuid),
+        "timestamp" => Ok(ColumnType::Timestamp),
+        "list" => Ok(ColumnType::List),
+        "set" => Ok(ColumnType::Set),
+        "map" => Ok(ColumnType::Map),
+        "udt" => Ok(ColumnType::Udt),
+        other => Err(anyhow!(
+            "Invalid column type: '{}'. Allowed values: bigint, text, blob, uuid, timestamp, list, set, map, udt",
+            other
+        )),
+    }
+}
+
+// Parses sizes like "100", "512B", "1KiB" or "2MiB", a plain number means bytes
+pub fn parse_size(text: &str) -> Result<usize> {
+    let digits_end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits_end);
+
+    let number: usize = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid size: '{}'", text))?;
+
+    let multiplier: usize = match unit {
+        "" | "B" => 1,
+        "KB" => 1000,
+        "KiB" => 1024,
+        "MB" => 1000 * 1000,
+        "MiB" => 1024 * 1024,
+        _ => {
+            return Err(anyhow!(
+                "Invalid size unit in '{}'. Allowed units: B, KB, KiB, MB, MiB",
+                text
+            ))
+        }
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("Size '{}' is too big", text))
+}
+
+// Parses durations like "60s", "500ms", "10m" or "1h", a plain number means seconds
+pub fn parse_duration(text: &str) -> Result<Duration> {
+    let digits_end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(digits_end);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid duration: '{}'", text))?;
+
+    let secs: f64 = match unit {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => {
+            return Err(anyhow!(
+                "Invalid duration unit in '{}'. Allowed units: ms, s, m, h",
+                text
+            ))
+        }
+    };
+
+    Duration::try_from_secs_f64(secs).map_err(|_| anyhow!("Duration '{}' is too long", text))
+}
+
+// Durations are reported in seconds
+fn serialize_secs<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distributions() {
+        assert_eq!(
+            parse_distribution("sequential").unwrap(),
+            KeyDistribution::Sequential
+        );
+        assert_eq!(
+            parse_distribution("uniform").unwrap(),
+            KeyDistribution::Uniform
+        );
+        assert_eq!(
+            parse_distribution("zipf").unwrap(),
+            KeyDistribution::Zipf { skew: 1.0 }
+        );
+        assert_eq!(
+            parse_distribution("zipf:1.2").unwrap(),
+            KeyDistribution::Zipf { skew: 1.2 }
+        );
+        assert_eq!(
+            parse_distribution("gaussian:0.05").unwrap(),
+            KeyDistribution::Gaussian { stddev: 0.05 }
+        );
+    }
+
+    #[test]
+    fn invalid_distributions() {
+        assert!(parse_distribution("pareto").is_err());
+        assert!(parse_distribution("uniform:2").is_err());
+        assert!(parse_distribution("zipf:abc").is_err());
+        assert!(parse_distribution("zipf:-1").is_err());
+        assert!(parse_distribution("gaussian:0").is_err());
+        assert!(parse_distribution("gaussian:inf").is_err());
+    }
+
+    fn parse_args(args: &[&str]) -> Result<Option<Config>> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Config::parse(&args, "127.0.0.1:9042")
+    }
+
+    #[test]
+    fn tasks_and_concurrency() {
+        let config = parse_args(&["--tasks", "100", "--concurrency", "10"])
+            .unwrap()
+            .unwrap();
+        assert_eq!((config.tasks, config.concurrency), (100, 10));
+
+        assert!(parse_args(&["--tasks", "0"]).is_err());
+        assert!(parse_args(&["--concurrency", "0"]).is_err());
+        assert!(parse_args(&["--concurrency", "-1"]).is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("60").unwrap(), Duration::from_secs(60));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("-1s").is_err());
+        assert!(parse_duration("1e30h").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("2KB").unwrap(), 2000);
+        assert_eq!(parse_size("1KiB").unwrap(), 1024);
+        assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+        assert_eq!(parse_size("2MiB").unwrap(), 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("KiB").is_err());
+        assert!(parse_size("1GiB").is_err());
+        assert!(parse_size("1.5KiB").is_err());
+        assert!(parse_size(&format!("{}MiB", usize::MAX / 1024)).is_err());
+    }
+
+    #[test]
+    fn replication() {
+        assert_eq!(
+            parse_replication("simple", "3").unwrap(),
+            Replication::Simple { factor: 3 }
+        );
+        assert_eq!(
+            parse_replication("network-topology", "dc1:3,dc2:1").unwrap(),
+            Replication::NetworkTopology {
+                factors: vec![("dc1".to_string(), 3), ("dc2".to_string(), 1)]
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_replication() {
+        assert!(parse_replication("simple", "0").is_err());
+        assert!(parse_replication("simple", "dc1:3").is_err());
+        assert!(parse_replication("network-topology", "3").is_err());
+        assert!(parse_replication("network-topology", ":3").is_err());
+        assert!(parse_replication("network-topology", "dc1:3,dc2:x").is_err());
+        assert!(parse_replication("local", "1").is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert!(validate_name("benchks_2").is_ok());
+        assert!(validate_name("Bench").is_err());
+        assert!(validate_name("2bench").is_err());
+        assert!(validate_name("bench-ks").is_err());
+        assert!(validate_name(&"a".repeat(49)).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+use std::fmt;
+
+// Classes of errors returned by failed requests
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    // The request or the server side read/write timed out
+    Timeout,
+    Overloaded,
+    // Not enough replicas were alive to achieve the consistency level
+    Unavailable,
+    // Broken or refused connection
+    Connection,
+    // The request succeeded, but returned different rows than expected
+    Verification,
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Overloaded => "overloaded",
+            ErrorKind::Unavailable => "unavailable",
+            ErrorKind::Connection => "connection",
+            ErrorKind::Verification => "verification",
+            ErrorKind::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+// Returned by drivers when a response doesn't hold the expected values, e.g. a value column is null.
+// It's classified as a failed verification of the rows.
+#[derive(Debug)]
+pub struct VerificationError(pub String);
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+pub fn verification_error(message: impl Into<String>) -> anyhow::Error {
+    VerificationError(message.into()).into()
+}
+
+// Driver independent classification, used by drivers which don't recognize their own errors.
+// Looks for verification errors, I/O errors and tokio timeouts among the causes of the error.
+pub fn classify_generic(error: &anyhow::Error) -> ErrorKind {
+    for cause in error.chain() {
+        if cause.is::<VerificationError>() {
+            return ErrorKind::Verification;
+        }
+
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return ErrorKind::Timeout;
+        }
+
+        if let Some(io_error) = cause.downcast_ref::<std::io::Error>() {
+            return match io_error.kind() {
+                std::io::ErrorKind::TimedOut => ErrorKind::Timeout,
+                _ => ErrorKind::Connection,
+            };
+        }
+    }
+
+    ErrorKind::Other
+}
+
+// Number of failed requests of one operation type, by error kind
+#[derive(Serialize, Default, Clone, Copy, Debug)]
+pub struct ErrorCounts {
+    pub timeout: u64,
+    pub overloaded: u64,
+    pub unavailable: u64,
+    pub connection: u64,
+    pub verification: u64,
+    pub other: u64,
+}
+
+impl ErrorCounts {
+    pub fn record(&mut self, kind: ErrorKind) {
+        match kind {
+            ErrorKind::Timeout => self.timeout += 1,
+            ErrorKind::Overloaded => self.overloaded += 1,
+            ErrorKind::Unavailable => self.unavailable += 1,
+            ErrorKind::Connection => self.connection += 1,
+            ErrorKind::Verification => self.verification += 1,
+            ErrorKind::Other => self.other += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &ErrorCounts) {
+        self.timeout += other.timeout;
+        self.overloaded += other.overloaded;
+        self.unavailable += other.unavailable;
+        self.connection += other.connection;
+        self.verification += other.verification;
+        self.other += other.other;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.timeout
+            + self.overloaded
+            + self.unavailable
+            + self.connection
+            + self.verification
+            + self.other
+    }
+
+    pub fn print(&self, name: &str) {
+        if self.total() == 0 {
+            return;
+        }
+
+        println!("\n{} ({} total):", name, self.total());
+
+        let counts = [
+            (ErrorKind::Timeout, self.timeout),
+            (ErrorKind::Overloaded, self.overloaded),
+            (ErrorKind::Unavailable, self.unavailable),
+            (ErrorKind::Connection, self.connection),
+            (ErrorKind::Verification, self.verification),
+            (ErrorKind::Other, self.other),
+        ];
+
+        for (kind, count) in counts.iter() {
+            if *count > 0 {
+                println!("  {:<12} {:>10}", kind, count);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn classify_verification_errors() {
+        assert_eq!(
+            classify_generic(&verification_error("v1 is null")),
+            ErrorKind::Verification
+        );
+
+        let wrapped: anyhow::Result<()> = Err(verification_error("v1 is null"));
+        let wrapped = wrapped.context("Select failed").unwrap_err();
+        assert_eq!(classify_generic(&wrapped), ErrorKind::Verification);
+
+        assert_eq!(classify_generic(&anyhow!("v1 is null")), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::KeyDistribution;
+use anyhow::{anyhow, Result};
+use rand::rngs::SmallRng;
+use rand::Rng;
+use rand_distr::{Distribution, Normal, Zipf};
+
+// Chooses keys from `0..key_count` according to the configured distribution
+pub enum KeySampler {
+    Sequential { key_count: i64 },
+    Uniform { key_count: i64 },
+    // Samples ranks from `1..=key_count`, rank 1 is the hottest key
+    Zipf(Zipf<f64>),
+    Gaussian { key_count: i64, normal: Normal<f64> },
+}
+
+impl KeySampler {
+    pub fn new(distribution: KeyDistribution, key_count: i64) -> Result<KeySampler> {
+        let sampler = match distribution {
+            KeyDistribution::Sequential => KeySampler::Sequential { key_count },
+            KeyDistribution::Uniform => KeySampler::Uniform { key_count },
+            KeyDistribution::Zipf { skew } => KeySampler::Zipf(
+                Zipf::new(key_count as u64, skew)
+                    .map_err(|e| anyhow!("Invalid zipf distribution: {}", e))?,
+            ),
+            KeyDistribution::Gaussian { stddev } => KeySampler::Gaussian {
+                key_count,
+                normal: Normal::new(key_count as f64 / 2.0, stddev * key_count as f64)
+                    .map_err(|e| anyhow!("Invalid gaussian distribution: {}", e))?,
+            },
+        };
+
+        Ok(sampler)
+    }
+
+    // Key operated on by task number `task`
+    pub fn key(&self, task: i64, rng: &mut SmallRng) -> i64 {
+        match self {
+            KeySampler::Sequential { key_count } => task % key_count,
+            _ => self.random_key(rng),
+        }
+    }
+
+    // A random key, sequential keys are replaced with uniformly random ones
+    pub fn random_key(&self, rng: &mut SmallRng) -> i64 {
+        match self {
+            KeySampler::Sequential { key_count } | KeySampler::Uniform { key_count } => {
+                rng.gen_range(0..*key_count)
+            }
+            KeySampler::Zipf(zipf) => zipf.sample(rng) as i64 - 1,
+            KeySampler::Gaussian { key_count, normal } => loop {
+                // Samples outside of the key range are drawn again, so that the edges don't get hot
+                let key = normal.sample(rng).floor();
+                if key >= 0.0 && key < *key_count as f64 {
+                    break key as i64;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn sample(distribution: KeyDistribution, key_count: i64) -> Vec<i64> {
+        let sampler = KeySampler::new(distribution, key_count).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        (0..10_000).map(|_| sampler.random_key(&mut rng)).collect()
+    }
+
+    #[test]
+    fn sequential_keys() {
+        let sampler = KeySampler::new(KeyDistribution::Sequential, 10).unwrap();
+        let mut rng = SmallRng::seed_from_u64(1);
+        assert_eq!(sampler.key(3, &mut rng), 3);
+        assert_eq!(sampler.key(13, &mut rng), 3);
+    }
+
+    #[test]
+    fn keys_are_in_range() {
+        for distribution in [
+            KeyDistribution::Uniform,
+            KeyDistribution::Zipf { skew: 1.2 },
+            KeyDistribution::Gaussian { stddev: 0.5 },
+        ] {
+            assert!(sample(distribution, 100)
+                .iter()
+                .all(|key| (0..100).contains(key)));
+        }
+    }
+
+    #[test]
+    fn zipf_prefers_low_keys() {
+        let keys = sample(KeyDistribution::Zipf { skew: 1.2 }, 1000);
+        let hottest = keys.iter().filter(|key| **key == 0).count();
+        let coldest = keys.iter().filter(|key| **key == 999).count();
+        assert!(hottest > 100 * coldest.max(1));
+    }
+
+    #[test]
+    fn gaussian_is_centered() {
+        let keys = sample(KeyDistribution::Gaussian { stddev: 0.05 }, 1000);
+        let mean = keys.iter().sum::<i64>() as f64 / keys.len() as f64;
+        assert!((mean - 500.0).abs() < 10.0);
+    }
+
+    #[test]
+    fn same_seed_gives_same_keys() {
+        assert_eq!(
+            sample(KeyDistribution::Uniform, 1000),
+            sample(KeyDistribution::Uniform, 1000)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Driver independent part of the `basic` benchmark.
+//!
+//! Each driver benchmark implements [`Driver`] and calls [`run`] from its `main`.
+//! Configuration, distribution of work between tasks, timing and reporting
+//! are done here, so that all drivers perform exactly the same work.
+
+mod benchmark;
+pub mod config;
+pub mod error;
+mod keys;
+pub mod report;
+pub mod schema;
+pub mod stats;
+#[cfg(feature = "tls")]
+pub mod tls;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use benchmark::Phase;
+use config::{Compression, Config, LoadBalancingSettings};
+use error::ErrorKind;
+use report::Report;
+use schema::{BatchInsert, Value};
+use stats::Stats;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Operations that a driver has to provide to be benchmarked.
+#[async_trait]
+pub trait Driver: Sized + Send + Sync + 'static {
+    /// Connection to the cluster, used to create the schema and prepare statements
+    type Session: Send + Sync;
+
+    /// Name printed at the start of the benchmark
+    const NAME: &'static str;
+
+    /// Node addresses used when `--nodes` isn't specified
+    const DEFAULT_NODES: &'static str;
+
+    async fn connect(config: &Config) -> Result<Self::Session>;
+
+    /// Load balancing used with `config`, with the defaults of the driver for options that weren't given.
+    /// Returns an error when the driver doesn't support the requested one.
+    fn load_balancing(config: &Config) -> Result<LoadBalancingSettings>;
+
+    /// Drops and recreates the keyspace with `config.schema.drop_keyspace_query()`,
+    /// `config.schema.create_keyspace_query()` and `config.schema.create_table_queries()`
+    async fn create_schema(session: &Self::Session, config: &Config) -> Result<()>;
+
+    /// Prepares the queries of `config.schema` used by the operations below
+    async fn prepare(session: Self::Session, config: &Config) -> Result<Self>;
+
+    /// Inserts a row with the given `pk` and values of all columns of the schema
+    async fn insert(&self, pk: i64, values: &[Value]) -> Result<()>;
+
+    /// Returns values of all columns of the row with the given `pk`, `None` if there's no such row
+    async fn select(&self, pk: i64) -> Result<Option<Vec<Value>>>;
+
+    /// Sets all columns of the row with the given `pk` using `config.schema.update_query()`
+    async fn update(&self, pk: i64, values: &[Value]) -> Result<()>;
+
+    /// Deletes the row with the given `pk`
+    async fn delete(&self, pk: i64) -> Result<()>;
+
+    /// Inserts a row into the partition `pk` of `widetab`
+    async fn insert_wide(&self, pk: i64, ck: i64, values: &[Value]) -> Result<()>;
+
+    /// Reads all rows of the partition `pk` of `widetab` fetching `config.page_size` rows at once.
+    /// Returns the number of rows, all of them have to be deserialized.
+    async fn scan(&self, pk: i64) -> Result<i64>;
+
+    /// Performs all inserts in a single BATCH statement of type `config.batch_type`
+    async fn batch(&self, inserts: &[BatchInsert]) -> Result<()>;
+
+    /// Inserts a row like `insert`, but only if it doesn't exist, using `config.schema.lwt_insert_query()`.
+    /// Returns the `[applied]` column of the result.
+    async fn lwt_insert(&self, pk: i64, values: &[Value]) -> Result<bool>;
+
+    /// Sets all columns like `update`, but only if `v1` is equal to `expected`,
+    /// using `config.schema.lwt_update_query()`. Returns the `[applied]` column of the result.
+    async fn lwt_update(&self, pk: i64, values: &[Value], expected: &Value) -> Result<bool>;
+
+    /// Adds `increment` to the counter `pk` of `countertab` using `config.schema.counter_update_query()`
+    async fn counter_update(&self, pk: i64, increment: i64) -> Result<()>;
+
+    /// Returns the value of the counter `pk`, `None` if it was never incremented
+    async fn counter_select(&self, pk: i64) -> Result<Option<i64>>;
+
+    /// Inserts a point of the sensor into `timeseries`
+    /// using `config.schema.timeseries_insert_query(config.ttl)`
+    async fn insert_point(&self, sensor_id: i64, day: i64, ts: i64, values: &[Value])
+        -> Result<()>;
+
+    /// Returns `ts` and values of the latest `limit` points of the sensor from the given day, newest first
+    async fn select_points(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, Vec<Value>)>>;
+
+    /// Compression of frames negotiated with the nodes, `None` if the driver doesn't expose it.
+    /// It can differ from `config.compression` when the nodes don't support the requested one.
+    fn compression(_session: &Self::Session, _config: &Config) -> Option<Compression> {
+        None
+    }
+
+    /// Classifies an error returned by one of the operations
+    fn classify_error(error: &anyhow::Error) -> ErrorKind {
+        error::classify_generic(error)
+    }
+}
+
+/// Runs the whole benchmark using the given driver
+pub async fn run<D: Driver>() -> Result<()> {
+    match Config::read(D::DEFAULT_NODES)? {
+        Some(config) => run_with_config::<D>(config).await,
+        None => Ok(()), // --help only prints usage
+    }
+}
+
+/// Runs the whole benchmark using the given driver with an already read configuration.
+/// Useful when the type of the driver depends on the configuration.
+pub async fn run_with_config<D: Driver>(config: Config) -> Result<()> {
+    println!("Starting {} benchmark\n", D::NAME);
+
+    let config: Arc<Config> = Arc::new(config);
+
+    println!("Benchmark configuration:\n{:#?}\n", config);
+
+    if config.tls && config.ca_cert.is_none() {
+        eprintln!(
+            "Warning: --tls without --ca-cert, the certificates of the nodes won't be verified\n"
+        );
+    }
+
+    let load_balancing: LoadBalancingSettings = D::load_balancing(&config)?;
+    println!("Load balancing:\n{:#?}\n", load_balancing);
+
+    let session = D::connect(&config).await?;
+
+    let compression: Option<Compression> = D::compression(&session, &config);
+    match compression {
+        Some(compression) => println!("Negotiated compression: {}", compression),
+        None => println!(
+            "Requested compression: {} (the driver doesn't report the negotiated one)",
+            config.compression
+        ),
+    }
+
+    if !config.dont_prepare {
+        D::create_schema(&session, &config).await?;
+    }
+
+    let driver: Arc<D> = Arc::new(D::prepare(session, &config).await?);
+
+    if config.needs_existing_rows() && !config.dont_prepare {
+        benchmark::prepare_existing_rows(&driver, &config).await?;
+    }
+
+    let mut warmup_stats: Option<Stats> = None;
+    if let Some(warmup) = Phase::warmup(&config) {
+        println!("\nWarming up");
+
+        let warmup_start = Instant::now();
+        let stats: Stats = benchmark::run_tasks(&driver, &config, warmup).await?;
+
+        println!(
+            "Warmup finished: {} operations in {} ms (results discarded)",
+            stats.operations(),
+            warmup_start.elapsed().as_millis()
+        );
+        warmup_stats = Some(stats);
+    }
+
+    println!("\nStarting the benchmark");
+
+    let start_time = Instant::now();
+
+    let stats: Stats = benchmark::run_tasks(&driver, &config, Phase::benchmark(&config)).await?;
+
+    let bench_time = start_time.elapsed();
+    println!("Finished\n\nBenchmark time: {} ms", bench_time.as_millis());
+
+    if let (Some(rate), None) = (config.rate, config.duration) {
+        let scheduled_time = Duration::from_secs_f64(config.tasks as f64 / rate);
+        println!("Scheduled time: {} ms", scheduled_time.as_millis());
+    }
+
+    println!(
+        "Operations: {}\nThroughput: {:.0} ops/s\nErrors: {}",
+        stats.operations(),
+        stats.operations() as f64 / bench_time.as_secs_f64(),
+        stats.errors()
+    );
+
+    stats.print();
+
+    Report::new(
+        D::NAME,
+        &config,
+        &load_balancing,
+        compression,
+        bench_time,
+        &stats,
+    )?
+    .write()?;
+
+    if config.verify_counters {
+        // Warmup increments are also included in the counters
+        let mut all_stats = Stats::default();
+        if let Some(warmup_stats) = &warmup_stats {
+            all_stats.merge(warmup_stats);
+        }
+        all_stats.merge(&stats);
+
+        benchmark::verify_counters(&driver, &config, &all_stats).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use crate::config::{Compression, Config, LoadBalancingSettings, OutputFormat};
+use crate::stats::{LatencySummary, Operation, Stats};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::Duration;
+
+// Machine-readable result of a single benchmark run
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub driver: &'a str,
+    pub config: &'a Config,
+    // Load balancing the driver used, including its defaults
+    pub load_balancing: &'a LoadBalancingSettings,
+    // Compression negotiated with the nodes, null if the driver doesn't report it
+    pub negotiated_compression: Option<Compression>,
+    pub time_ms: u128,
+    pub operations: u64,
+    pub ops_per_sec: f64,
+    pub errors: u64,
+    // `<operation>_latency_us` fields of all operations, e.g. `insert_latency_us`
+    #[serde(flatten)]
+    pub latencies: Map<String, Value>,
+    pub schedule_lag_us: LatencySummary,
+    // `<operation>_applied` and `<operation>_not_applied` fields of conditional operations
+    #[serde(flatten)]
+    pub applied: Map<String, Value>,
+    // `<operation>_errors` fields of all operations, e.g. `insert_errors`
+    #[serde(flatten)]
+    pub operation_errors: Map<String, Value>,
+}
+
+impl<'a> Report<'a> {
+    pub fn new(
+        driver: &'a str,
+        config: &'a Config,
+        load_balancing: &'a LoadBalancingSettings,
+        negotiated_compression: Option<Compression>,
+        bench_time: Duration,
+        stats: &Stats,
+    ) -> Result<Self> {
+        let operations = stats.operations();
+
+        let mut latencies = Map::new();
+        let mut operation_errors = Map::new();
+        let mut applied = Map::new();
+        for operation in Operation::ALL.iter() {
+            latencies.insert(
+                format!("{}_latency_us", operation.name()),
+                serde_json::to_value(stats.latency_summary(*operation))?,
+            );
+            operation_errors.insert(
+                format!("{}_errors", operation.name()),
+                serde_json::to_value(stats.operation(*operation).errors)?,
+            );
+
+            if operation.is_conditional() {
+                let operation_stats = stats.operation(*operation);
+                applied.insert(
+                    format!("{}_applied", operation.name()),
+                    operation_stats.applied.into(),
+                );
+                applied.insert(
+                    format!("{}_not_applied", operation.name()),
+                    operation_stats.not_applied.into(),
+                );
+            }
+        }
+
+        Ok(Report {
+            driver,
+            config,
+            load_balancing,
+            negotiated_compression,
+            time_ms: bench_time.as_millis(),
+            operations,
+            ops_per_sec: operations as f64 / bench_time.as_secs_f64(),
+            errors: stats.errors(),
+            latencies,
+            schedule_lag_us: stats.schedule_lag_summary(),
+            applied,
+            operation_errors,
+        })
+    }
+
+    // Prints the report to stdout or appends it to the output file, depending on the configuration
+    pub fn write(&self) -> Result<()> {
+        let file_is_empty = match &self.config.output_file {
+            Some(path) => std::fs::metadata(path)
+                .map(|m| m.len() == 0)
+                .unwrap_or(true),
+            None => true,
+        };
+
+        let output: String = match self.config.output_format {
+            OutputFormat::Text => return Ok(()),
+            OutputFormat::Json => format!("{}\n", serde_json::to_string(self)?),
+            OutputFormat::Csv => {
+                let (header, values) = self.to_csv()?;
+
+                // A header is written only once, so that results of many runs can be appended to one file
+                if file_is_empty {
+                    format!("{}\n{}\n", header, values)
+                } else {
+                    format!("{}\n", values)
+                }
+            }
+        };
+
+        match &self.config.output_file {
+            Some(path) => {
+                let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+                file.write_all(output.as_bytes())?;
+            }
+            None => print!("\n{}", output),
+        }
+
+        Ok(())
+    }
+
+    // Flattens the report into one CSV row, nested fields are named with a dot e.g. `config.tasks`
+    fn to_csv(&self) -> Result<(String, String)> {
+        let mut columns: Vec<(String, String)> = Vec::new();
+        flatten(String::new(), &serde_json::to_value(self)?, &mut columns);
+
+        let header: Vec<String> = columns.iter().map(|(name, _)| csv_field(name)).collect();
+        let values: Vec<String> = columns.iter().map(|(_, value)| csv_field(value)).collect();
+
+        Ok((header.join(","), values.join(",")))
+    }
+}
+
+fn flatten(prefix: String, value: &Value, columns: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields {
+                let name = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(name, field, columns);
+            }
+        }
+        Value::Array(elements) => {
+            let joined: Vec<String> = elements.iter().map(plain_value).collect();
+            columns.push((prefix, joined.join(" ")));
+        }
+        other => columns.push((prefix, plain_value(other))),
+    }
+}
+
+fn plain_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Columns keep the order of the fields in the report
+    #[test]
+    fn flatten_nested_fields() {
+        let mut columns = Vec::new();
+        flatten(
+            String::new(),
+            &json!({
+                "tasks": 100,
+                "config": {"workload": "inserts", "hosts": ["a", "b"], "rate": null},
+            }),
+            &mut columns,
+        );
+
+        assert_eq!(
+            columns,
+            vec![
+                ("tasks".to_string(), "100".to_string()),
+                ("config.workload".to_string(), "inserts".to_string()),
+                ("config.hosts".to_string(), "a b".to_string()),
+                ("config.rate".to_string(), String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
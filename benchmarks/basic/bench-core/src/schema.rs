@@ -0,0 +1,356 @@
+// Keyspace and tables of the benchmark, columns of the main table and the values stored in them.
+//
+// The main table (`benchtab` by default) always has a `pk bigint` primary key followed by the configured value columns
+// `v1, v2, ...`. Values of a row are generated from its `pk`, so that selected rows can be verified.
+
+use rand::distributions::Alphanumeric;
+use rand::rngs::SmallRng;
+use rand::{Rng, RngCore, SeedableRng};
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Bigint,
+    Text,
+    Blob,
+    Uuid,
+    Timestamp,
+    // list<int>
+    List,
+    // set<text>
+    Set,
+    // map<text, bigint>
+    Map,
+    // frozen<benchudt>, see `Schema::udt_query`
+    Udt,
+}
+
+impl ColumnType {
+    pub fn cql_name(&self) -> &'static str {
+        match self {
+            ColumnType::Bigint => "bigint",
+            ColumnType::Text => "text",
+            ColumnType::Blob => "blob",
+            ColumnType::Uuid => "uuid",
+            ColumnType::Timestamp => "timestamp",
+            ColumnType::List => "list<int>",
+            ColumnType::Set => "set<text>",
+            ColumnType::Map => "map<text, bigint>",
+            ColumnType::Udt => "frozen<benchudt>",
+        }
+    }
+}
+
+// Value of a single column
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Value {
+    Bigint(i64),
+    Text(String),
+    Blob(Vec<u8>),
+    Uuid([u8; 16]),
+    // Milliseconds since the unix epoch
+    Timestamp(i64),
+    List(Vec<i32>),
+    // Sorted like sets returned by the database
+    Set(Vec<String>),
+    // Sorted by the keys
+    Map(Vec<(String, i64)>),
+    Udt { id: i64, name: String },
+}
+
+// One insert of a batch, into `widetab` if it has a clustering key
+// and into the main table otherwise
+pub struct BatchInsert {
+    pub pk: i64,
+    pub ck: Option<i64>,
+    pub values: Vec<Value>,
+}
+
+// Replication strategy of the keyspace
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Replication {
+    Simple { factor: u32 },
+    // Replication factor of every datacenter
+    NetworkTopology { factors: Vec<(String, u32)> },
+}
+
+// Written as the replication map of CREATE KEYSPACE, so that it's a single CSV column
+impl fmt::Display for Replication {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Replication::Simple { factor } => write!(
+                f,
+                "{{'class' : 'SimpleStrategy', 'replication_factor' : {}}}",
+                factor
+            ),
+            Replication::NetworkTopology { factors } => {
+                write!(f, "{{'class' : 'NetworkTopologyStrategy'")?;
+                for (datacenter, factor) in factors {
+                    write!(f, ", '{}' : {}", datacenter, factor)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Serialize for Replication {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Schema {
+    // The keyspace is dropped and recreated before the benchmark, unless it's run with --dont-prepare
+    pub keyspace: String,
+    pub replication: Replication,
+    // Name of the main table, the other tables have fixed names in the same keyspace
+    pub table: String,
+    // Types of the value columns `v1, v2, ...`
+    pub columns: Vec<ColumnType>,
+    // Size of text and blob values in bytes, also of texts inside of collections and udts
+    pub value_size: usize,
+    // Number of elements of lists, sets and maps
+    pub collection_size: usize,
+}
+
+impl Schema {
+    pub fn drop_keyspace_query(&self) -> String {
+        format!("DROP KEYSPACE IF EXISTS {}", self.keyspace)
+    }
+
+    pub fn create_keyspace_query(&self) -> String {
+        format!(
+            "CREATE KEYSPACE IF NOT EXISTS {} WITH REPLICATION = {}",
+            self.keyspace, self.replication
+        )
+    }
+
+    // User defined type of the udt columns
+    pub fn udt_query(&self) -> String {
+        format!(
+            "CREATE TYPE IF NOT EXISTS {}.benchudt (id bigint, name text)",
+            self.keyspace
+        )
+    }
+
+    // Creates the main table, `widetab`, which has a clustering key for range scans,
+    // `countertab` with a single counter column and `timeseries`,
+    // which has a partition for each sensor and day with the newest points first.
+    // The user defined type is created first, if any column uses it.
+    pub fn create_table_queries(&self) -> Vec<String> {
+        let columns: String = self
+            .column_names()
+            .iter()
+            .zip(self.columns.iter())
+            .map(|(name, column_type)| format!(", {} {}", name, column_type.cql_name()))
+            .collect();
+
+        let mut queries = Vec::new();
+        if self.columns.contains(&ColumnType::Udt) {
+            queries.push(self.udt_query());
+        }
+
+        queries.extend(vec![
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.{} (pk bigint PRIMARY KEY{})",
+                self.keyspace, self.table, columns
+            ),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.widetab (pk bigint, ck bigint{}, PRIMARY KEY (pk, ck))",
+                self.keyspace, columns
+            ),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.countertab (pk bigint PRIMARY KEY, c counter)",
+                self.keyspace
+            ),
+            format!(
+                "CREATE TABLE IF NOT EXISTS {}.timeseries (sensor_id bigint, day bigint, ts timestamp{}, \
+                PRIMARY KEY ((sensor_id, day), ts)) WITH CLUSTERING ORDER BY (ts DESC)",
+                self.keyspace, columns
+            ),
+        ]);
+
+        queries
+    }
+
+    // Binds `pk` followed by the values of all columns
+    pub fn insert_query(&self) -> String {
+        let markers = vec!["?"; self.columns.len() + 1];
+
+        format!(
+            "INSERT INTO {}.{} (pk, {}) VALUES({})",
+            self.keyspace,
+            self.table,
+            self.column_names().join(", "),
+            markers.join(", ")
+        )
+    }
+
+    // Selects all value columns of the row with the bound `pk`
+    pub fn select_query(&self) -> String {
+        format!(
+            "SELECT {} FROM {}.{} WHERE pk = ?",
+            self.column_names().join(", "),
+            self.keyspace,
+            self.table
+        )
+    }
+
+    // Binds the values of all columns followed by `pk`
+    pub fn update_query(&self) -> String {
+        let assignments: Vec<String> = self
+            .column_names()
+            .iter()
+            .map(|name| format!("{} = ?", name))
+            .collect();
+
+        format!(
+            "UPDATE {}.{} SET {} WHERE pk = ?",
+            self.keyspace,
+            self.table,
+            assignments.join(", ")
+        )
+    }
+
+    // Deletes the whole row with the bound `pk`
+    pub fn delete_query(&self) -> String {
+        format!("DELETE FROM {}.{} WHERE pk = ?", self.keyspace, self.table)
+    }
+
+    // Like `insert_query`, but applied only if there's no row with the bound `pk` yet
+    pub fn lwt_insert_query(&self) -> String {
+        format!("{} IF NOT EXISTS", self.insert_query())
+    }
+
+    // Like `update_query`, but applied only if `v1` is equal to the value bound after `pk`
+    pub fn lwt_update_query(&self) -> String {
+        format!("{} IF v1 = ?", self.update_query())
+    }
+
+    // Binds `pk`, `ck` and the values of all columns
+    pub fn insert_wide_query(&self) -> String {
+        let markers = vec!["?"; self.columns.len() + 2];
+
+        format!(
+            "INSERT INTO {}.widetab (pk, ck, {}) VALUES({})",
+            self.keyspace,
+            self.column_names().join(", "),
+            markers.join(", ")
+        )
+    }
+
+    // Selects all rows of the partition with the bound `pk`
+    pub fn scan_query(&self) -> String {
+        format!(
+            "SELECT {} FROM {}.widetab WHERE pk = ?",
+            self.column_names().join(", "),
+            self.keyspace
+        )
+    }
+
+    // Binds the increment followed by `pk`
+    pub fn counter_update_query(&self) -> String {
+        format!(
+            "UPDATE {}.countertab SET c = c + ? WHERE pk = ?",
+            self.keyspace
+        )
+    }
+
+    pub fn counter_select_query(&self) -> String {
+        format!("SELECT c FROM {}.countertab WHERE pk = ?", self.keyspace)
+    }
+
+    // Binds `sensor_id`, `day`, `ts` and the values of all columns.
+    // `day` is the number of days since the unix epoch.
+    pub fn timeseries_insert_query(&self, ttl: i32) -> String {
+        let markers = vec!["?"; self.columns.len() + 3];
+
+        format!(
+            "INSERT INTO {}.timeseries (sensor_id, day, ts, {}) VALUES({}) USING TTL {}",
+            self.keyspace,
+            self.column_names().join(", "),
+            markers.join(", "),
+            ttl
+        )
+    }
+
+    // Selects `ts` and all value columns of the latest points, binds `sensor_id`, `day` and the limit
+    pub fn timeseries_select_query(&self) -> String {
+        format!(
+            "SELECT ts, {} FROM {}.timeseries WHERE sensor_id = ? AND day = ? LIMIT ?",
+            self.column_names().join(", "),
+            self.keyspace
+        )
+    }
+
+    // Values stored in the row with the given `pk`, always the same for the same `pk`
+    pub fn row(&self, pk: i64) -> Vec<Value> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(index, column_type)| self.value(pk, index, *column_type))
+            .collect()
+    }
+
+    fn column_names(&self) -> Vec<String> {
+        (1..=self.columns.len())
+            .map(|i| format!("v{}", i))
+            .collect()
+    }
+
+    fn value(&self, pk: i64, index: usize, column_type: ColumnType) -> Value {
+        // Bigint values are the same as in the original `(pk, v1, v2)` table: v1 = 2 * pk, v2 = 3 * pk
+        let multiplier = index as i64 + 2;
+        let mut rng = SmallRng::seed_from_u64((pk as u64).wrapping_mul(1024) ^ index as u64);
+
+        match column_type {
+            ColumnType::Bigint => Value::Bigint(multiplier.wrapping_mul(pk)),
+            ColumnType::Text => Value::Text(self.text(&mut rng)),
+            ColumnType::Blob => {
+                let mut blob = vec![0; self.value_size];
+                rng.fill_bytes(&mut blob);
+                Value::Blob(blob)
+            }
+            ColumnType::Uuid => {
+                let mut uuid = [0; 16];
+                rng.fill_bytes(&mut uuid);
+
+                // Random (version 4) UUID with the RFC 4122 variant
+                uuid[6] = (uuid[6] & 0x0f) | 0x40;
+                uuid[8] = (uuid[8] & 0x3f) | 0x80;
+                Value::Uuid(uuid)
+            }
+            ColumnType::Timestamp => Value::Timestamp(multiplier.wrapping_mul(pk)),
+            ColumnType::List => Value::List((0..self.collection_size).map(|_| rng.gen()).collect()),
+            // Random texts are practically always distinct, duplicates would just make the set smaller
+            ColumnType::Set => {
+                let set: BTreeSet<String> = (0..self.collection_size)
+                    .map(|_| self.text(&mut rng))
+                    .collect();
+                Value::Set(set.into_iter().collect())
+            }
+            ColumnType::Map => {
+                let map: BTreeMap<String, i64> = (0..self.collection_size)
+                    .map(|_| (self.text(&mut rng), rng.gen()))
+                    .collect();
+                Value::Map(map.into_iter().collect())
+            }
+            ColumnType::Udt => Value::Udt {
+                id: multiplier.wrapping_mul(pk),
+                name: self.text(&mut rng),
+            },
+        }
+    }
+
+    fn text(&self, rng: &mut SmallRng) -> String {
+        rng.sample_iter(Alphanumeric)
+            .take(self.value_size)
+            .map(char::from)
+            .collect()
+    }
+}
@@ -0,0 +1,244 @@
+use crate::error::{ErrorCounts, ErrorKind};
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::time::Duration;
+
+// Latencies are recorded in microseconds, anything above an hour is saturated
+const MAX_LATENCY: u64 = 3_600_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+const PERCENTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 99.99];
+
+// Latency percentiles of one operation type, in microseconds
+#[derive(Serialize, Debug)]
+pub struct LatencySummary {
+    pub samples: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p99_9: u64,
+    pub p99_99: u64,
+    pub max: u64,
+}
+
+// Types of requests, latencies and errors are recorded separately for each of them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Insert,
+    Select,
+    Update,
+    Delete,
+    // Reading a whole partition, all of its pages
+    Scan,
+    // A whole BATCH statement
+    Batch,
+    // Conditional statements, which go through Paxos
+    LwtInsert,
+    LwtUpdate,
+    CounterUpdate,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 9] = [
+        Operation::Insert,
+        Operation::Select,
+        Operation::Update,
+        Operation::Delete,
+        Operation::Scan,
+        Operation::Batch,
+        Operation::LwtInsert,
+        Operation::LwtUpdate,
+        Operation::CounterUpdate,
+    ];
+
+    // Used in names of the report fields, e.g. `insert_latency_us`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Select => "select",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+            Operation::Scan => "scan",
+            Operation::Batch => "batch",
+            Operation::LwtInsert => "lwt_insert",
+            Operation::LwtUpdate => "lwt_update",
+            Operation::CounterUpdate => "counter_update",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Operation::Insert => "Insert",
+            Operation::Select => "Select",
+            Operation::Update => "Update",
+            Operation::Delete => "Delete",
+            Operation::Scan => "Scan",
+            Operation::Batch => "Batch",
+            Operation::LwtInsert => "LWT insert",
+            Operation::LwtUpdate => "LWT update",
+            Operation::CounterUpdate => "Counter update",
+        }
+    }
+
+    // Whether the result of the operation says if it was applied
+    pub fn is_conditional(&self) -> bool {
+        matches!(self, Operation::LwtInsert | Operation::LwtUpdate)
+    }
+}
+
+pub struct OperationStats {
+    pub latency: Histogram<u64>,
+    // Failed requests aren't included in the latency histogram
+    pub errors: ErrorCounts,
+    // Successful conditional requests by the `[applied]` column of their result
+    pub applied: u64,
+    pub not_applied: u64,
+}
+
+impl Default for OperationStats {
+    fn default() -> OperationStats {
+        OperationStats {
+            latency: Histogram::new_with_max(MAX_LATENCY, SIGNIFICANT_DIGITS).unwrap(),
+            errors: ErrorCounts::default(),
+            applied: 0,
+            not_applied: 0,
+        }
+    }
+}
+
+pub struct Stats {
+    // Indexed by `Operation as usize`
+    operations: Vec<OperationStats>,
+    // How late requests were started compared to the schedule, only with a fixed rate
+    pub schedule_lag: Histogram<u64>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats {
+            operations: Operation::ALL
+                .iter()
+                .map(|_| OperationStats::default())
+                .collect(),
+            schedule_lag: Histogram::new_with_max(MAX_LATENCY, SIGNIFICANT_DIGITS).unwrap(),
+        }
+    }
+}
+
+impl Stats {
+    pub fn record(&mut self, operation: Operation, latency: Duration) {
+        record(&mut self.operations[operation as usize].latency, latency);
+    }
+
+    pub fn record_applied(&mut self, operation: Operation, applied: bool) {
+        let stats = &mut self.operations[operation as usize];
+        if applied {
+            stats.applied += 1;
+        } else {
+            stats.not_applied += 1;
+        }
+    }
+
+    pub fn record_error(&mut self, operation: Operation, kind: ErrorKind) {
+        self.operations[operation as usize].errors.record(kind);
+    }
+
+    pub fn record_schedule_lag(&mut self, lag: Duration) {
+        record(&mut self.schedule_lag, lag);
+    }
+
+    pub fn operation(&self, operation: Operation) -> &OperationStats {
+        &self.operations[operation as usize]
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        for (mine, theirs) in self.operations.iter_mut().zip(other.operations.iter()) {
+            mine.latency.add(&theirs.latency).unwrap();
+            mine.errors.merge(&theirs.errors);
+            mine.applied += theirs.applied;
+            mine.not_applied += theirs.not_applied;
+        }
+        self.schedule_lag.add(&other.schedule_lag).unwrap();
+    }
+
+    // Total number of successfully performed requests
+    pub fn operations(&self) -> u64 {
+        self.operations.iter().map(|op| op.latency.len()).sum()
+    }
+
+    // Total number of failed requests
+    pub fn errors(&self) -> u64 {
+        self.operations.iter().map(|op| op.errors.total()).sum()
+    }
+
+    pub fn latency_summary(&self, operation: Operation) -> LatencySummary {
+        summarize(&self.operation(operation).latency)
+    }
+
+    pub fn schedule_lag_summary(&self) -> LatencySummary {
+        summarize(&self.schedule_lag)
+    }
+
+    pub fn print(&self) {
+        for operation in Operation::ALL.iter() {
+            let name = format!("{} latency", operation.title());
+            print_histogram(&name, &self.operation(*operation).latency);
+        }
+
+        print_histogram("Schedule lag", &self.schedule_lag);
+
+        for operation in Operation::ALL.iter() {
+            let stats = self.operation(*operation);
+            if operation.is_conditional() && stats.applied + stats.not_applied > 0 {
+                println!(
+                    "\n{}s applied: {}, not applied: {}",
+                    operation.title(),
+                    stats.applied,
+                    stats.not_applied
+                );
+            }
+        }
+
+        for operation in Operation::ALL.iter() {
+            let name = format!("{} errors", operation.title());
+            self.operation(*operation).errors.print(&name);
+        }
+    }
+}
+
+fn record(histogram: &mut Histogram<u64>, latency: Duration) {
+    let micros: u64 = latency.as_micros().try_into().unwrap_or(u64::MAX);
+    histogram.saturating_record(micros);
+}
+
+// All values are 0 if there were no requests of this type
+fn summarize(histogram: &Histogram<u64>) -> LatencySummary {
+    LatencySummary {
+        samples: histogram.len(),
+        p50: histogram.value_at_percentile(PERCENTILES[0]),
+        p90: histogram.value_at_percentile(PERCENTILES[1]),
+        p99: histogram.value_at_percentile(PERCENTILES[2]),
+        p99_9: histogram.value_at_percentile(PERCENTILES[3]),
+        p99_99: histogram.value_at_percentile(PERCENTILES[4]),
+        max: histogram.max(),
+    }
+}
+
+fn print_histogram(name: &str, histogram: &Histogram<u64>) {
+    if histogram.is_empty() {
+        return;
+    }
+
+    println!("\n{} ({} samples):", name, histogram.len());
+
+    for percentile in PERCENTILES.iter() {
+        println!(
+            "  p{:<6} {:>10} us",
+            percentile,
+            histogram.value_at_percentile(*percentile)
+        );
+    }
+
+    println!("  max     {:>10} us", histogram.max());
+}
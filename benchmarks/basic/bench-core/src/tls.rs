@@ -0,0 +1,21 @@
+// Reading of PEM files for TLS with rustls
+
+use anyhow::{anyhow, Result};
+use rustls::internal::pemfile;
+use rustls::PrivateKey;
+
+// Reads a PKCS #8 or an RSA private key
+pub fn private_key(path: &str) -> Result<PrivateKey> {
+    let pem = std::fs::read(path)?;
+
+    let mut keys = pemfile::pkcs8_private_keys(&mut pem.as_slice())
+        .map_err(|_| anyhow!("Invalid private key file: {}", path))?;
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut pem.as_slice())
+            .map_err(|_| anyhow!("Invalid private key file: {}", path))?;
+    }
+
+    keys.into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("No private key found in {}", path))
+}
@@ -5,4 +5,5 @@ if ! [ -x "$(command -v podman)" ]; then
     CONTAINER_TOOL="docker"
 fi
 
-$CONTAINER_TOOL build "$@" . -t rust-driver-benchmarks-basic-cassandra-cpp
+# The build context is the parent directory, which holds the Cargo workspace of all Rust benchmarks
+$CONTAINER_TOOL build "$@" -f Dockerfile .. -t rust-driver-benchmarks-basic-cassandra-cpp
@@ -1,179 +1,844 @@
-mod config;
-
-use anyhow::Result;
-use cassandra_cpp::{Cluster, PreparedStatement, Session, Statement};
-use config::{Config, Workload};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bench_core::config::{
+    BatchType, Compression, Config, Consistency, LoadBalancing, LoadBalancingSettings,
+    SerialConsistency,
+};
+use bench_core::error::{self, ErrorKind};
+use bench_core::schema::{BatchInsert, ColumnType, Value};
+use bench_core::Driver;
+use cassandra_cpp::{
+    Batch, BatchType as CassBatchType, CassCollection, CassResult, Cluster,
+    Consistency as CassConsistency, DataType, List, Map, PreparedStatement, Row, Session, Set, Ssl,
+    SslVerifyFlag, Statement, Uuid, ValueType,
+};
+use std::cell::RefCell;
 use std::convert::TryInto;
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::sync::Arc;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Number of IO threads, one per CPU, and connections to each node opened by every one of them.
+// The pool of the driver is per IO thread, so --connections-per-host has to be divided evenly between
+// the threads. The number of threads isn't changed to fit it, because that would be measured as well.
+fn io_threads(config: &Config) -> Result<(u32, Option<u32>)> {
+    let threads: u32 = num_cpus::get().try_into()?;
+
+    match config.connections_per_host {
+        Some(connections) if connections % threads != 0 => Err(anyhow!(
+            "cassandra-cpp opens connections from each of its {} IO threads (one per CPU), \
+             --connections-per-host has to be a multiple of {}",
+            threads,
+            threads
+        )),
+        Some(connections) => Ok((threads, Some(connections / threads))),
+        None => Ok((threads, None)),
+    }
+}
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    println!("Starting cassandra-cpp benchmark\n");
+// cassandra_cpp::Error isn't Sync, so it can't be converted to anyhow::Error using `?`
+fn cass_err(err: cassandra_cpp::Error) -> anyhow::Error {
+    anyhow!("{}", err)
+}
 
-    let config: Arc<Config> = match Config::read()? {
-        Some(config) => Arc::new(config),
-        None => return Ok(()), // --help only prints usage
-    };
+// Statements bound from a prepared statement, which are reused by the following requests.
+// Binding a new statement for every request would add to the measured overhead of the driver.
+struct StatementPool {
+    prepared: PreparedStatement,
+    // Index of the free statements of this pool in `FREE_STATEMENTS`
+    id: usize,
+}
+
+static NEXT_POOL_ID: AtomicUsize = AtomicUsize::new(0);
 
-    println!("Benchmark configuration:\n{:#?}\n", config);
+thread_local! {
+    // Every thread keeps its own free statements, so that tasks don't contend for a lock.
+    // A statement can be returned on another thread than the one it was taken on.
+    static FREE_STATEMENTS: RefCell<Vec<Vec<Statement>>> = const { RefCell::new(Vec::new()) };
+}
 
-    let mut cluster = Cluster::default();
+impl StatementPool {
+    fn new(prepared: PreparedStatement) -> StatementPool {
+        StatementPool {
+            prepared,
+            id: NEXT_POOL_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
 
-    for node_address in &config.node_addresses {
-        cluster.set_contact_points(&node_address).unwrap();
+    fn take(&self) -> Option<Statement> {
+        FREE_STATEMENTS.with(|free| free.borrow_mut().get_mut(self.id).and_then(Vec::pop))
+    }
+
+    // All values are bound again by the next request, so it doesn't matter how this one finished
+    fn put(&self, stmt: Statement) {
+        FREE_STATEMENTS.with(|free| {
+            let mut free = free.borrow_mut();
+            if free.len() <= self.id {
+                free.resize_with(self.id + 1, Vec::new);
+            }
+            free[self.id].push(stmt);
+        });
     }
+}
+
+// A statement taken from a pool, which is returned to it when dropped, also after a failed request
+struct PooledStatement<'a> {
+    pool: &'a StatementPool,
+    stmt: Option<Statement>,
+}
 
-    cluster.set_queue_size_io(std::cmp::max(2048, (2 * config.concurrency).try_into().unwrap())).unwrap();
-    cluster.set_num_threads_io(num_cpus::get().try_into().unwrap()).unwrap();
+impl Deref for PooledStatement<'_> {
+    type Target = Statement;
 
-    let session: Arc<Session> = Arc::new(cluster.connect_async().await.unwrap());
+    fn deref(&self) -> &Statement {
+        self.stmt.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledStatement<'_> {
+    fn deref_mut(&mut self) -> &mut Statement {
+        self.stmt.as_mut().unwrap()
+    }
+}
 
-    if !config.dont_prepare {
-        prepare_keyspace_and_table(&session).await?;
+impl Drop for PooledStatement<'_> {
+    fn drop(&mut self) {
+        if let Some(stmt) = self.stmt.take() {
+            self.pool.put(stmt);
+        }
     }
+}
 
-    let insert_stmt = "INSERT INTO benchks.benchtab (pk, v1, v2) VALUES(?, ?, ?)";
-    let select_stmt = "SELECT v1, v2 FROM benchks.benchtab WHERE pk = ?";
+struct CassandraCppDriver {
+    session: Session,
+    prepared_insert: StatementPool,
+    prepared_select: StatementPool,
+    prepared_update: StatementPool,
+    prepared_delete: StatementPool,
+    prepared_insert_wide: StatementPool,
+    prepared_scan: PreparedStatement,
+    prepared_lwt_insert: StatementPool,
+    prepared_lwt_update: StatementPool,
+    prepared_counter_update: StatementPool,
+    prepared_counter_select: StatementPool,
+    prepared_insert_point: StatementPool,
+    prepared_select_points: StatementPool,
+    // Needed to know the types of selected values
+    columns: Vec<ColumnType>,
+    page_size: i32,
+    batch_type: CassBatchType,
+    same_partition_batches: bool,
+    // None means the default of the driver
+    consistency: Option<CassConsistency>,
+    serial_consistency: Option<CassConsistency>,
+}
 
-    let prepared_insert = session.prepare(insert_stmt).unwrap().await.unwrap();
-    let prepared_select = session.prepare(select_stmt).unwrap().await.unwrap();
+#[async_trait]
+impl Driver for CassandraCppDriver {
+    type Session = Session;
+
+    const NAME: &'static str = "cassandra-cpp";
+    const DEFAULT_NODES: &'static str = "127.0.0.1";
+
+    async fn connect(config: &Config) -> Result<Session> {
+        if config.compression != Compression::None {
+            return Err(anyhow!(
+                "cassandra-cpp doesn't support compression of frames"
+            ));
+        }
+
+        if config.connections_per_shard.is_some() {
+            return Err(anyhow!(
+                "cassandra-cpp isn't shard aware, use --connections-per-host instead"
+            ));
+        }
+
+        let mut cluster = Cluster::default();
+
+        for node_address in &config.node_addresses {
+            cluster.set_contact_points(node_address).map_err(cass_err)?;
+        }
+
+        cluster
+            .set_queue_size_io(std::cmp::max(2048, (2 * config.concurrency).try_into()?))
+            .map_err(cass_err)?;
+
+        let (threads, connections_per_thread) = io_threads(config)?;
+        cluster.set_num_threads_io(threads).map_err(cass_err)?;
+        if let Some(connections) = connections_per_thread {
+            cluster
+                .set_core_connections_per_host(connections)
+                .map_err(cass_err)?;
+            println!(
+                "cassandra-cpp: {} IO threads with {} connections to each node, {} in total",
+                threads,
+                connections,
+                threads * connections
+            );
+        }
+
+        let load_balancing = Self::load_balancing(config)?;
+        match (load_balancing.policy, &load_balancing.local_dc) {
+            (LoadBalancing::RoundRobin, _) => {
+                cluster.set_load_balance_round_robin();
+            }
+            (LoadBalancing::DcAware, Some(local_dc)) => {
+                cluster
+                    .set_load_balance_dc_aware::<()>(local_dc, 0, false)
+                    .map_err(cass_err)?;
+            }
+            // The default policy, the local datacenter is the one of the first contacted node
+            (LoadBalancing::DcAware, None) => {}
+        }
+        cluster.set_token_aware_routing(load_balancing.token_aware);
+
+        if config.tls {
+            cluster.set_ssl(&mut ssl(config)?);
+        }
+        if let (Some(user), Some(password)) = (&config.user, &config.password) {
+            cluster
+                .set_credentials(user, password.as_str())
+                .map_err(cass_err)?;
+        }
+
+        cluster.connect_async().await.map_err(cass_err)
+    }
 
-    if config.workload == Workload::Selects && !config.dont_prepare {
-        prepare_selects_benchmark(&session, &prepared_insert, &config).await?;
+    // Token aware dc-aware policy is the default of the driver
+    fn load_balancing(config: &Config) -> Result<LoadBalancingSettings> {
+        if config.shard_aware == Some(true) {
+            return Err(anyhow!("cassandra-cpp isn't shard aware"));
+        }
+
+        Ok(LoadBalancingSettings {
+            policy: config.load_balancing.unwrap_or(LoadBalancing::DcAware),
+            local_dc: config.local_dc.clone(),
+            token_aware: config.token_aware.unwrap_or(true),
+            shard_aware: false,
+        })
     }
 
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
+    async fn create_schema(session: &Session, config: &Config) -> Result<()> {
+        let drop_ks = Statement::new(&config.schema.drop_keyspace_query(), 0);
 
-    println!("\nStarting the benchmark");
+        session.execute(&drop_ks).await.map_err(cass_err)?;
 
-    let start_time = std::time::Instant::now();
+        let create_ks = Statement::new(&config.schema.create_keyspace_query(), 0);
 
-    for _ in 0..config.concurrency {
-        let session = session.clone();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
-        let mut insert_stmt = prepared_insert.bind();
-        let mut select_stmt = prepared_select.bind();
+        session.execute(&create_ks).await.map_err(cass_err)?;
 
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
+        for create_table_query in config.schema.create_table_queries() {
+            let create_table = Statement::new(&create_table_query, 0);
 
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+            session.execute(&create_table).await.map_err(cass_err)?;
+        }
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
+        Ok(())
+    }
 
-                for pk in cur_batch_start..cur_batch_end {
-                    if config.workload == Workload::Inserts || config.workload == Workload::Mixed {
-                        insert_stmt.bind_int64(0, pk).unwrap();
-                        insert_stmt.bind_int64(1, 2 * pk).unwrap();
-                        insert_stmt.bind_int64(2, 3 * pk).unwrap();
+    async fn prepare(session: Session, config: &Config) -> Result<CassandraCppDriver> {
+        let insert_stmt = config.schema.insert_query();
+        let select_stmt = config.schema.select_query();
+        let update_stmt = config.schema.update_query();
+        let delete_stmt = config.schema.delete_query();
+        let insert_wide_stmt = config.schema.insert_wide_query();
+        let scan_stmt = config.schema.scan_query();
+        let lwt_insert_stmt = config.schema.lwt_insert_query();
+        let lwt_update_stmt = config.schema.lwt_update_query();
+        let counter_update_stmt = config.schema.counter_update_query();
+        let counter_select_stmt = config.schema.counter_select_query();
+        let insert_point_stmt = config.schema.timeseries_insert_query(config.ttl);
+        let select_points_stmt = config.schema.timeseries_select_query();
+
+        let prepared_insert = session
+            .prepare(&insert_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_select = session
+            .prepare(&select_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_update = session
+            .prepare(&update_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_delete = session
+            .prepare(&delete_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_insert_wide = session
+            .prepare(&insert_wide_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_scan = session
+            .prepare(&scan_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_lwt_insert = session
+            .prepare(&lwt_insert_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_lwt_update = session
+            .prepare(&lwt_update_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_counter_update = session
+            .prepare(&counter_update_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_counter_select = session
+            .prepare(&counter_select_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_insert_point = session
+            .prepare(&insert_point_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+        let prepared_select_points = session
+            .prepare(&select_points_stmt)
+            .map_err(cass_err)?
+            .await
+            .map_err(cass_err)?;
+
+        Ok(CassandraCppDriver {
+            session,
+            prepared_insert: StatementPool::new(prepared_insert),
+            prepared_select: StatementPool::new(prepared_select),
+            prepared_update: StatementPool::new(prepared_update),
+            prepared_delete: StatementPool::new(prepared_delete),
+            prepared_insert_wide: StatementPool::new(prepared_insert_wide),
+            prepared_scan,
+            prepared_lwt_insert: StatementPool::new(prepared_lwt_insert),
+            prepared_lwt_update: StatementPool::new(prepared_lwt_update),
+            prepared_counter_update: StatementPool::new(prepared_counter_update),
+            prepared_counter_select: StatementPool::new(prepared_counter_select),
+            prepared_insert_point: StatementPool::new(prepared_insert_point),
+            prepared_select_points: StatementPool::new(prepared_select_points),
+            columns: config.schema.columns.clone(),
+            page_size: config.page_size,
+            batch_type: match config.batch_type {
+                BatchType::Logged => CassBatchType::LOGGED,
+                BatchType::Unlogged => CassBatchType::UNLOGGED,
+            },
+            same_partition_batches: config.same_partition_batches,
+            consistency: config.consistency.map(to_cass_consistency),
+            serial_consistency: config.serial_consistency.map(to_cass_serial_consistency),
+        })
+    }
 
-                        let fut = session.execute(&insert_stmt);
-                        fut.await.unwrap();
-                    }
+    async fn insert(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut insert_stmt = self.statement(&self.prepared_insert)?;
+        insert_stmt.bind_int64(0, pk).map_err(cass_err)?;
+        self.bind_values(&mut insert_stmt, 1, values)?;
 
-                    if config.workload == Workload::Selects || config.workload == Workload::Mixed {
-                        select_stmt.bind_int64(0, pk).unwrap();
+        let fut = self.session.execute(&insert_stmt);
+        fut.await.map_err(cass_err)?;
 
-                        let fut = session.execute(&select_stmt);
-                        let res = fut.await.unwrap();
-                        let first_row = res.first_row().unwrap();
+        Ok(())
+    }
 
-                        let v1: i64 = first_row.get_column(0).unwrap().get_i64().unwrap();
-                        let v2: i64 = first_row.get_column(1).unwrap().get_i64().unwrap();
+    async fn select(&self, pk: i64) -> Result<Option<Vec<Value>>> {
+        let mut select_stmt = self.statement(&self.prepared_select)?;
+        select_stmt.bind_int64(0, pk).map_err(cass_err)?;
+
+        let fut = self.session.execute(&select_stmt);
+        let res = fut.await.map_err(cass_err)?;
+        let first_row = match res.first_row() {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let values = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column_type)| get_value(&first_row, index, *column_type))
+            .collect::<Result<_>>()?;
+
+        Ok(Some(values))
+    }
 
-                        assert_eq!((v1, v2), (2 * pk, 3 * pk));
-                    }
+    async fn update(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut update_stmt = self.statement(&self.prepared_update)?;
+        self.bind_values(&mut update_stmt, 0, values)?;
+        update_stmt.bind_int64(values.len(), pk).map_err(cass_err)?;
+
+        let fut = self.session.execute(&update_stmt);
+        fut.await.map_err(cass_err)?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, pk: i64) -> Result<()> {
+        let mut delete_stmt = self.statement(&self.prepared_delete)?;
+        delete_stmt.bind_int64(0, pk).map_err(cass_err)?;
+
+        let fut = self.session.execute(&delete_stmt);
+        fut.await.map_err(cass_err)?;
+
+        Ok(())
+    }
+
+    async fn insert_wide(&self, pk: i64, ck: i64, values: &[Value]) -> Result<()> {
+        let mut insert_stmt = self.statement(&self.prepared_insert_wide)?;
+        insert_stmt.bind_int64(0, pk).map_err(cass_err)?;
+        insert_stmt.bind_int64(1, ck).map_err(cass_err)?;
+        self.bind_values(&mut insert_stmt, 2, values)?;
+
+        let fut = self.session.execute(&insert_stmt);
+        fut.await.map_err(cass_err)?;
+
+        Ok(())
+    }
+
+    async fn scan(&self, pk: i64) -> Result<i64> {
+        // Not reused, the statement keeps the paging state of the last fetched page
+        let mut scan_stmt = self.new_statement(&self.prepared_scan)?;
+        scan_stmt.bind_int64(0, pk).map_err(cass_err)?;
+        scan_stmt
+            .set_paging_size(self.page_size)
+            .map_err(cass_err)?;
+
+        let mut row_count: i64 = 0;
+        loop {
+            let fut = self.session.execute(&scan_stmt);
+            let res = fut.await.map_err(cass_err)?;
+
+            for row in res.iter() {
+                for (index, column_type) in self.columns.iter().enumerate() {
+                    get_value(&row, index, *column_type)?;
                 }
+                row_count += 1;
             }
-        }));
+
+            if !res.has_more_pages() {
+                break;
+            }
+
+            // The next execution fetches the following page
+            scan_stmt.set_paging_state(res).map_err(cass_err)?;
+        }
+
+        Ok(row_count)
     }
 
-    for handle in handles {
-        handle.await?;
+    async fn batch(&self, inserts: &[BatchInsert]) -> Result<()> {
+        let prepared = match self.same_partition_batches {
+            true => &self.prepared_insert_wide.prepared,
+            false => &self.prepared_insert.prepared,
+        };
+
+        let mut batch = Batch::new(self.batch_type);
+        // Consistency levels of the statements are ignored, only the ones of the batch count
+        if let Some(consistency) = self.consistency {
+            batch.set_consistency(consistency).map_err(cass_err)?;
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            batch
+                .set_serial_consistency(serial_consistency)
+                .map_err(cass_err)?;
+        }
+
+        for insert in inserts {
+            // Batches keep references to their statements, so they aren't reused
+            let mut insert_stmt = prepared.bind();
+            insert_stmt.bind_int64(0, insert.pk).map_err(cass_err)?;
+
+            let values_start = match insert.ck {
+                Some(ck) => {
+                    insert_stmt.bind_int64(1, ck).map_err(cass_err)?;
+                    2
+                }
+                None => 1,
+            };
+            self.bind_values(&mut insert_stmt, values_start, &insert.values)?;
+
+            batch.add_statement(&insert_stmt).map_err(cass_err)?;
+        }
+
+        let fut = self.session.execute_batch(&batch);
+        fut.await.map_err(cass_err)?;
+
+        Ok(())
     }
 
-    let bench_time = start_time.elapsed();
-    println!("Finished\n\nBenchmark time: {} ms", bench_time.as_millis());
+    async fn lwt_insert(&self, pk: i64, values: &[Value]) -> Result<bool> {
+        let mut insert_stmt = self.statement(&self.prepared_lwt_insert)?;
+        insert_stmt.bind_int64(0, pk).map_err(cass_err)?;
+        self.bind_values(&mut insert_stmt, 1, values)?;
 
-    Ok(())
-}
+        let fut = self.session.execute(&insert_stmt);
+        let res = fut.await.map_err(cass_err)?;
 
-async fn prepare_keyspace_and_table(session: &Session) -> Result<()> {
-    let drop_ks = Statement::new("DROP KEYSPACE IF EXISTS benchks", 0);
+        applied(&res)
+    }
 
-    session.execute(&drop_ks).await.unwrap();
+    async fn lwt_update(&self, pk: i64, values: &[Value], expected: &Value) -> Result<bool> {
+        let mut update_stmt = self.statement(&self.prepared_lwt_update)?;
+        self.bind_values(&mut update_stmt, 0, values)?;
+        update_stmt.bind_int64(values.len(), pk).map_err(cass_err)?;
+        self.bind_values(
+            &mut update_stmt,
+            values.len() + 1,
+            std::slice::from_ref(expected),
+        )?;
 
-    let create_ks = Statement::new(
-        "CREATE KEYSPACE IF NOT EXISTS benchks WITH REPLICATION = \
-    {'class' : 'SimpleStrategy', 'replication_factor' : 1}",
-        0,
-    );
+        let fut = self.session.execute(&update_stmt);
+        let res = fut.await.map_err(cass_err)?;
 
-    session.execute(&create_ks).await.unwrap();
+        applied(&res)
+    }
 
-    let create_table = Statement::new(
-        "CREATE TABLE IF NOT EXISTS benchks.benchtab (pk bigint PRIMARY KEY, v1 bigint, v2 bigint)",
-        0,
-    );
+    async fn counter_update(&self, pk: i64, increment: i64) -> Result<()> {
+        let mut update_stmt = self.statement(&self.prepared_counter_update)?;
+        // Counters are bound the same way as bigints
+        update_stmt.bind_int64(0, increment).map_err(cass_err)?;
+        update_stmt.bind_int64(1, pk).map_err(cass_err)?;
 
-    session.execute(&create_table).await.unwrap();
+        let fut = self.session.execute(&update_stmt);
+        fut.await.map_err(cass_err)?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-async fn prepare_selects_benchmark(
-    session: &Arc<Session>,
-    prepared_insert: &PreparedStatement,
-    config: &Arc<Config>,
-) -> Result<()> {
-    println!("Preparing a selects benchmark (inserting values)...");
+    async fn counter_select(&self, pk: i64) -> Result<Option<i64>> {
+        let mut select_stmt = self.statement(&self.prepared_counter_select)?;
+        select_stmt.bind_int64(0, pk).map_err(cass_err)?;
 
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
+        let fut = self.session.execute(&select_stmt);
+        let res = fut.await.map_err(cass_err)?;
+        let first_row = match res.first_row() {
+            Some(row) => row,
+            None => return Ok(None),
+        };
 
-    for _ in 0..std::cmp::max(1024, config.concurrency) {
-        let session = session.clone();
-        let mut insert_stmt = prepared_insert.bind();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
+        let value = first_row
+            .get_column(0)
+            .map_err(cass_err)?
+            .get_i64()
+            .map_err(cass_err)?;
 
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
+        Ok(Some(value))
+    }
 
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+    async fn insert_point(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        ts: i64,
+        values: &[Value],
+    ) -> Result<()> {
+        let mut insert_stmt = self.statement(&self.prepared_insert_point)?;
+        insert_stmt.bind_int64(0, sensor_id).map_err(cass_err)?;
+        insert_stmt.bind_int64(1, day).map_err(cass_err)?;
+        // Timestamps are bound the same way as bigints
+        insert_stmt.bind_int64(2, ts).map_err(cass_err)?;
+        self.bind_values(&mut insert_stmt, 3, values)?;
+
+        let fut = self.session.execute(&insert_stmt);
+        fut.await.map_err(cass_err)?;
+
+        Ok(())
+    }
+
+    async fn select_points(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, Vec<Value>)>> {
+        let mut select_stmt = self.statement(&self.prepared_select_points)?;
+        select_stmt.bind_int64(0, sensor_id).map_err(cass_err)?;
+        select_stmt.bind_int64(1, day).map_err(cass_err)?;
+        select_stmt.bind_int32(2, limit).map_err(cass_err)?;
+
+        let fut = self.session.execute(&select_stmt);
+        let res = fut.await.map_err(cass_err)?;
+
+        let mut points = Vec::new();
+        for row in res.iter() {
+            let ts = row
+                .get_column(0)
+                .map_err(cass_err)?
+                .get_i64()
+                .map_err(cass_err)?;
+
+            // Value columns follow `ts`
+            let values = self
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(index, column_type)| get_value(&row, index + 1, *column_type))
+                .collect::<Result<_>>()?;
+            points.push((ts, values));
+        }
+
+        Ok(points)
+    }
+
+    // Frames are never compressed, the driver doesn't support it
+    fn compression(_session: &Session, _config: &Config) -> Option<Compression> {
+        Some(Compression::None)
+    }
+
+    // Only the message survives `cass_err`, it contains the name of the error code
+    fn classify_error(err: &anyhow::Error) -> ErrorKind {
+        let message = err.to_string();
+
+        if message.contains("TIMED_OUT") || message.contains("TIMEOUT") {
+            ErrorKind::Timeout
+        } else if message.contains("SERVER_OVERLOADED") {
+            ErrorKind::Overloaded
+        } else if message.contains("SERVER_UNAVAILABLE") {
+            ErrorKind::Unavailable
+        } else if message.contains("LIB_NO_HOSTS_AVAILABLE")
+            || message.contains("LIB_UNABLE_TO_CONNECT")
+            || message.contains("LIB_WRITE_ERROR")
+        {
+            ErrorKind::Connection
+        } else {
+            error::classify_generic(err)
+        }
+    }
+}
+
+impl CassandraCppDriver {
+    // Takes a free statement of the pool or binds a new one
+    fn statement<'a>(&self, pool: &'a StatementPool) -> Result<PooledStatement<'a>> {
+        let stmt = match pool.take() {
+            Some(stmt) => stmt,
+            None => self.new_statement(&pool.prepared)?,
+        };
+        Ok(PooledStatement {
+            pool,
+            stmt: Some(stmt),
+        })
+    }
+
+    // Creates a statement from the prepared one with the consistency levels from the configuration
+    fn new_statement(&self, prepared: &PreparedStatement) -> Result<Statement> {
+        let mut stmt = prepared.bind();
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
+        if let Some(consistency) = self.consistency {
+            stmt.set_consistency(consistency).map_err(cass_err)?;
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            stmt.set_serial_consistency(serial_consistency)
+                .map_err(cass_err)?;
+        }
 
-                for pk in cur_batch_start..cur_batch_end {
-                    insert_stmt.bind_int64(0, pk).unwrap();
-                    insert_stmt.bind_int64(1, 2 * pk).unwrap();
-                    insert_stmt.bind_int64(2, 3 * pk).unwrap();
+        Ok(stmt)
+    }
 
-                    let fut = session.execute(&insert_stmt);
-                    fut.await.unwrap();
+    // Binds the values to consecutive bind markers starting at `first_index`
+    fn bind_values(
+        &self,
+        stmt: &mut Statement,
+        first_index: usize,
+        values: &[Value],
+    ) -> Result<()> {
+        for (index, value) in values.iter().enumerate() {
+            let index = first_index + index;
+            match value {
+                Value::Bigint(v) => stmt.bind_int64(index, *v),
+                Value::Text(v) => stmt.bind_string(index, v),
+                Value::Blob(v) => stmt.bind_bytes(index, v.clone()),
+                Value::Uuid(v) => stmt.bind_uuid(index, to_cass_uuid(v)?),
+                // Timestamps are bound the same way as bigints
+                Value::Timestamp(v) => stmt.bind_int64(index, *v),
+                Value::List(v) => {
+                    let mut list = List::with_capacity(v.len());
+                    for element in v {
+                        list.append_int32(*element).map_err(cass_err)?;
+                    }
+                    stmt.bind_list(index, list)
+                }
+                Value::Set(v) => {
+                    let mut set = Set::with_capacity(v.len());
+                    for element in v {
+                        set.append_string(element).map_err(cass_err)?;
+                    }
+                    stmt.bind_set(index, set)
+                }
+                Value::Map(v) => {
+                    let mut map = Map::with_capacity(v.len());
+                    for (key, value) in v {
+                        map.append_string(key).map_err(cass_err)?;
+                        map.append_int64(*value).map_err(cass_err)?;
+                    }
+                    stmt.bind_map(index, map)
+                }
+                Value::Udt { id, name } => {
+                    // Built like `Schema::udt_query` defines benchudt. A DataType isn't Sync, so it can't
+                    // be kept in the driver, and the one in the schema metadata only lives as long as it.
+                    let udt_type = DataType::new_udt(2);
+                    udt_type
+                        .add_sub_value_type_by_name("id", ValueType::BIGINT)
+                        .map_err(cass_err)?;
+                    udt_type
+                        .add_sub_value_type_by_name("name", ValueType::TEXT)
+                        .map_err(cass_err)?;
+
+                    let mut udt = udt_type.new_user_type();
+                    udt.set_int64_by_name("id", *id).map_err(cass_err)?;
+                    udt.set_string_by_name("name", name.as_str())
+                        .map_err(cass_err)?;
+                    stmt.bind_user_type(index, &udt)
                 }
             }
-        }));
+            .map_err(cass_err)?;
+        }
+
+        Ok(())
     }
+}
 
-    for handle in handles {
-        handle.await?;
+// The driver takes the contents of the PEM files instead of their paths
+fn ssl(config: &Config) -> Result<Ssl> {
+    let mut ssl = Ssl::default();
+
+    match &config.ca_cert {
+        Some(ca_cert) => {
+            ssl.add_trusted_cert(&std::fs::read_to_string(ca_cert)?)
+                .map_err(cass_err)?;
+            ssl.set_verify_flags(&[SslVerifyFlag::PEER_CERT]);
+        }
+        None => ssl.set_verify_flags(&[SslVerifyFlag::NONE]),
     }
 
-    Ok(())
+    if let (Some(client_cert), Some(client_key)) = (&config.client_cert, &config.client_key) {
+        ssl.set_cert(&std::fs::read_to_string(client_cert)?)
+            .map_err(cass_err)?;
+        // Encrypted private keys aren't supported
+        ssl.set_private_key(&std::fs::read_to_string(client_key)?, "")
+            .map_err(cass_err)?;
+    }
+
+    Ok(ssl)
+}
+
+fn to_cass_consistency(consistency: Consistency) -> CassConsistency {
+    match consistency {
+        Consistency::Any => CassConsistency::ANY,
+        Consistency::One => CassConsistency::ONE,
+        Consistency::Two => CassConsistency::TWO,
+        Consistency::Three => CassConsistency::THREE,
+        Consistency::Quorum => CassConsistency::QUORUM,
+        Consistency::All => CassConsistency::ALL,
+        Consistency::LocalQuorum => CassConsistency::LOCAL_QUORUM,
+        Consistency::EachQuorum => CassConsistency::EACH_QUORUM,
+        Consistency::LocalOne => CassConsistency::LOCAL_ONE,
+    }
+}
+
+// Serial consistency levels are variants of the same enum in cassandra-cpp
+fn to_cass_serial_consistency(serial_consistency: SerialConsistency) -> CassConsistency {
+    match serial_consistency {
+        SerialConsistency::Serial => CassConsistency::SERIAL,
+        SerialConsistency::LocalSerial => CassConsistency::LOCAL_SERIAL,
+    }
+}
+
+fn get_value(row: &Row, index: usize, column_type: ColumnType) -> Result<Value> {
+    let column = row.get_column(index).map_err(cass_err)?;
+    if column.is_null() {
+        return Err(error::verification_error(format!("v{} is null", index + 1)));
+    }
+
+    let value = match column_type {
+        ColumnType::Bigint => Value::Bigint(column.get_i64().map_err(cass_err)?),
+        ColumnType::Text => Value::Text(column.get_string().map_err(cass_err)?),
+        ColumnType::Blob => Value::Blob(column.get_bytes().map_err(cass_err)?.to_vec()),
+        ColumnType::Uuid => Value::Uuid(from_cass_uuid(column.get_uuid().map_err(cass_err)?)?),
+        ColumnType::Timestamp => Value::Timestamp(column.get_i64().map_err(cass_err)?),
+        // Lists are iterated the same way as sets
+        ColumnType::List => Value::List(
+            column
+                .get_set()
+                .map_err(cass_err)?
+                .map(|element| element.get_i32().map_err(cass_err))
+                .collect::<Result<_>>()?,
+        ),
+        ColumnType::Set => Value::Set(
+            column
+                .get_set()
+                .map_err(cass_err)?
+                .map(|element| element.get_string().map_err(cass_err))
+                .collect::<Result<_>>()?,
+        ),
+        ColumnType::Map => Value::Map(
+            column
+                .get_map()
+                .map_err(cass_err)?
+                .map(|(key, value)| {
+                    Ok((
+                        key.get_string().map_err(cass_err)?,
+                        value.get_i64().map_err(cass_err)?,
+                    ))
+                })
+                .collect::<Result<_>>()?,
+        ),
+        ColumnType::Udt => {
+            let mut id = None;
+            let mut name = None;
+            for (field, value) in column.get_user_type().map_err(cass_err)? {
+                match field.as_str() {
+                    "id" => id = Some(value.get_i64().map_err(cass_err)?),
+                    "name" => name = Some(value.get_string().map_err(cass_err)?),
+                    _ => {}
+                }
+            }
+
+            match (id, name) {
+                (Some(id), Some(name)) => Value::Udt { id, name },
+                _ => {
+                    return Err(error::verification_error(format!(
+                        "v{} is missing udt fields",
+                        index + 1
+                    )))
+                }
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+// The columns after `[applied]` differ between Scylla and Cassandra, so it's read by index
+fn applied(res: &CassResult) -> Result<bool> {
+    let row = res
+        .first_row()
+        .ok_or_else(|| anyhow!("Conditional statement didn't return the [applied] column"))?;
+
+    row.get_column(0)
+        .map_err(cass_err)?
+        .get_bool()
+        .map_err(cass_err)
+}
+
+// The driver's Uuid can only be converted from and to its string form
+fn to_cass_uuid(bytes: &[u8; 16]) -> Result<Uuid> {
+    uuid::Uuid::from_bytes(*bytes)
+        .to_string()
+        .parse::<Uuid>()
+        .map_err(cass_err)
+}
+
+fn from_cass_uuid(uuid: Uuid) -> Result<[u8; 16]> {
+    Ok(*uuid::Uuid::parse_str(&uuid.to_string())?.as_bytes())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    bench_core::run::<CassandraCppDriver>().await
 }
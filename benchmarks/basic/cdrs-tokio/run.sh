@@ -5,4 +5,11 @@ if ! [ -x "$(command -v podman)" ]; then
     CONTAINER_TOOL="docker"
 fi
 
-$CONTAINER_TOOL run --rm -it --network host rust-driver-benchmarks-basic-cdrs-tokio /source/basic "$@"
\ No newline at end of file
+# Certificates used for TLS have to be in CERTS_DIR, which is mounted at the same path in the container
+VOLUMES=""
+if [ -n "$CERTS_DIR" ]; then
+    VOLUMES="-v $CERTS_DIR:$CERTS_DIR:ro"
+fi
+
+# Credentials from the environment are passed through, so they stay out of the shell history
+$CONTAINER_TOOL run --rm -it --network host $VOLUMES -e BENCH_USER -e BENCH_PASSWORD rust-driver-benchmarks-basic-cdrs-tokio /source/basic "$@"
\ No newline at end of file
@@ -1,198 +1,691 @@
-mod config;
-
-use anyhow::Result;
-use cdrs_tokio::authenticators::NoneAuthenticator;
-use cdrs_tokio::cluster::session::{new as new_session, Session as CdrsSession};
-use cdrs_tokio::cluster::{ClusterTcpConfig, ConnectionPool, NodeTcpConfigBuilder};
+mod tls;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bench_core::config::{
+    BatchType, Compression, Config, Consistency, LoadBalancing, LoadBalancingSettings,
+    SerialConsistency,
+};
+use bench_core::error::{self, ErrorKind};
+use bench_core::schema::{BatchInsert, ColumnType, Value};
+use bench_core::Driver;
+use cdrs_tokio::authenticators::{Authenticator, NoneAuthenticator, StaticPasswordAuthenticator};
+use cdrs_tokio::cluster::session::{
+    new as new_session, new_lz4, new_lz4_tls, new_snappy, new_snappy_tls,
+    new_tls as new_tls_session, Session as CdrsSession,
+};
+use cdrs_tokio::cluster::{
+    ClusterRustlsConfig, ClusterTcpConfig, ConnectionPool, NodeRustlsConfigBuilder,
+    NodeTcpConfigBuilder,
+};
+use cdrs_tokio::consistency::Consistency as CdrsConsistency;
+use cdrs_tokio::error::Error as CdrsError;
+use cdrs_tokio::frame::frame_batch::BatchType as CdrsBatchType;
+use cdrs_tokio::frame::frame_error::AdditionalErrorInfo;
+use cdrs_tokio::frame::frame_result::RowsMetadataFlag;
+use cdrs_tokio::frame::Frame;
 use cdrs_tokio::load_balancing::RoundRobin;
 use cdrs_tokio::query::*;
 use cdrs_tokio::query_values;
 use cdrs_tokio::retry::DefaultRetryPolicy;
-use cdrs_tokio::transport::TransportTcp;
-use cdrs_tokio::types::IntoRustByIndex;
-use config::{Config, Workload};
-use std::convert::TryInto;
-use std::sync::atomic::{AtomicI64, Ordering};
+use cdrs_tokio::transport::{CdrsTransport, TransportRustls, TransportTcp};
+use cdrs_tokio::types::blob::Blob;
+use cdrs_tokio::types::list::List;
+use cdrs_tokio::types::map::Map;
+use cdrs_tokio::types::rows::Row;
+use cdrs_tokio::types::udt::Udt;
+use cdrs_tokio::types::value::{Bytes, Value as CdrsValue};
+use cdrs_tokio::types::CBytes;
+use cdrs_tokio::types::{AsRustType, IntoRustByIndex, IntoRustByName};
+use std::collections::HashMap;
+use std::net::ToSocketAddrs;
 use std::sync::Arc;
+use uuid::Uuid;
+
+type Session<T> = CdrsSession<RoundRobin<ConnectionPool<T>>>;
+
+// Generic over the transport, which is plain TCP or TLS
+struct CdrsDriver<T: Transport> {
+    session: Session<T>,
+    prepared_insert: PreparedQuery,
+    prepared_select: PreparedQuery,
+    prepared_update: PreparedQuery,
+    prepared_delete: PreparedQuery,
+    prepared_insert_wide: PreparedQuery,
+    prepared_scan: PreparedQuery,
+    prepared_lwt_insert: PreparedQuery,
+    prepared_lwt_update: PreparedQuery,
+    prepared_counter_update: PreparedQuery,
+    prepared_counter_select: PreparedQuery,
+    prepared_insert_point: PreparedQuery,
+    prepared_select_points: PreparedQuery,
+    // Needed to know the types of selected values
+    columns: Vec<ColumnType>,
+    page_size: i32,
+    batch_type: CdrsBatchType,
+    same_partition_batches: bool,
+    // None means the default of the driver
+    consistency: Option<CdrsConsistency>,
+    serial_consistency: Option<CdrsConsistency>,
+}
 
-type Session = CdrsSession<RoundRobin<ConnectionPool<TransportTcp>>>;
+#[async_trait]
+impl<T: Transport> Driver for CdrsDriver<T> {
+    type Session = Session<T>;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    println!("Starting cdrs-tokio benchmark\n");
+    const NAME: &'static str = "cdrs-tokio";
+    const DEFAULT_NODES: &'static str = "127.0.0.1:9042";
 
-    let config: Arc<Config> = match Config::read()? {
-        Some(config) => Arc::new(config),
-        None => return Ok(()), // --help only prints usage
-    };
+    async fn connect(config: &Config) -> Result<Session<T>> {
+        if config.connections_per_shard.is_some() {
+            return Err(anyhow!(
+                "cdrs-tokio isn't shard aware, use --connections-per-host instead"
+            ));
+        }
 
-    println!("Benchmark configuration:\n{:#?}\n", config);
+        T::connect(config).await
+    }
 
-    let mut nodes = Vec::new();
-    for addr in &config.node_addresses {
-        let node = NodeTcpConfigBuilder::new(&addr, Arc::new(NoneAuthenticator {})).build();
-        nodes.push(node);
+    // cdrs-tokio 4 only balances requests between all nodes round robin
+    fn load_balancing(config: &Config) -> Result<LoadBalancingSettings> {
+        if config.load_balancing == Some(LoadBalancing::DcAware)
+            || config.token_aware == Some(true)
+            || config.shard_aware == Some(true)
+        {
+            return Err(anyhow!(
+                "cdrs-tokio only supports round-robin load balancing, without token or shard awareness"
+            ));
+        }
+
+        Ok(LoadBalancingSettings {
+            policy: LoadBalancing::RoundRobin,
+            local_dc: None,
+            token_aware: false,
+            shard_aware: false,
+        })
     }
 
-    let cluster_config = ClusterTcpConfig(nodes);
-    let session: Session = new_session(
-        &cluster_config,
-        RoundRobin::new(),
-        Box::new(DefaultRetryPolicy::default()),
-    )
-    .await?;
+    async fn create_schema(session: &Session<T>, config: &Config) -> Result<()> {
+        session.query(config.schema.drop_keyspace_query()).await?;
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(4)).await; // Await schema agreement
+
+        session.query(config.schema.create_keyspace_query()).await?;
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+
+        for create_table in config.schema.create_table_queries() {
+            session.query(create_table).await?;
 
-    let session = Arc::new(session);
+            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        }
 
-    if !config.dont_prepare {
-        prepare_keyspace_and_table(&session).await?;
+        Ok(())
     }
 
-    let insert_stmt = "INSERT INTO benchks.benchtab (pk, v1, v2) VALUES(?, ?, ?)";
-    let select_stmt = "SELECT v1, v2 FROM benchks.benchtab WHERE pk = ?";
+    async fn prepare(session: Session<T>, config: &Config) -> Result<CdrsDriver<T>> {
+        let prepared_insert = session.prepare(config.schema.insert_query()).await?;
+        let prepared_select = session.prepare(config.schema.select_query()).await?;
+        let prepared_update = session.prepare(config.schema.update_query()).await?;
+        let prepared_delete = session.prepare(config.schema.delete_query()).await?;
+        let prepared_insert_wide = session.prepare(config.schema.insert_wide_query()).await?;
+        let prepared_scan = session.prepare(config.schema.scan_query()).await?;
+        let prepared_lwt_insert = session.prepare(config.schema.lwt_insert_query()).await?;
+        let prepared_lwt_update = session.prepare(config.schema.lwt_update_query()).await?;
+        let prepared_counter_update = session
+            .prepare(config.schema.counter_update_query())
+            .await?;
+        let prepared_counter_select = session
+            .prepare(config.schema.counter_select_query())
+            .await?;
+        let prepared_insert_point = session
+            .prepare(config.schema.timeseries_insert_query(config.ttl))
+            .await?;
+        let prepared_select_points = session
+            .prepare(config.schema.timeseries_select_query())
+            .await?;
+
+        Ok(CdrsDriver {
+            session,
+            prepared_insert,
+            prepared_select,
+            prepared_update,
+            prepared_delete,
+            prepared_insert_wide,
+            prepared_scan,
+            prepared_lwt_insert,
+            prepared_lwt_update,
+            prepared_counter_update,
+            prepared_counter_select,
+            prepared_insert_point,
+            prepared_select_points,
+            columns: config.schema.columns.clone(),
+            page_size: config.page_size,
+            batch_type: match config.batch_type {
+                BatchType::Logged => CdrsBatchType::Logged,
+                BatchType::Unlogged => CdrsBatchType::Unlogged,
+            },
+            same_partition_batches: config.same_partition_batches,
+            consistency: config.consistency.map(to_cdrs_consistency),
+            serial_consistency: config.serial_consistency.map(to_cdrs_serial_consistency),
+        })
+    }
+
+    async fn insert(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut cdrs_values: Vec<CdrsValue> = Vec::with_capacity(values.len() + 1);
+        cdrs_values.push(pk.into());
+        cdrs_values.extend(values.iter().map(to_cdrs_value));
 
-    let prepared_insert = Arc::new(session.prepare(insert_stmt).await?);
-    let prepared_select = Arc::new(session.prepare(select_stmt).await?);
+        self.session
+            .exec_with_params(
+                &self.prepared_insert,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
 
-    if config.workload == Workload::Selects && !config.dont_prepare {
-        prepare_selects_benchmark(&session, &prepared_insert, &config).await?;
+        Ok(())
     }
 
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
+    async fn select(&self, pk: i64) -> Result<Option<Vec<Value>>> {
+        let first_row = self
+            .session
+            .exec_with_params(&self.prepared_select, self.params(query_values!(pk)))
+            .await?
+            .body()?
+            .into_rows()
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?
+            .into_iter()
+            .next();
+
+        let first_row = match first_row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let values = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column_type)| get_value(&first_row, index, *column_type))
+            .collect::<Result<_>>()?;
+
+        Ok(Some(values))
+    }
 
-    println!("\nStarting the benchmark");
+    async fn update(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut cdrs_values: Vec<CdrsValue> = values.iter().map(to_cdrs_value).collect();
+        cdrs_values.push(pk.into());
 
-    let start_time = std::time::Instant::now();
+        self.session
+            .exec_with_params(
+                &self.prepared_update,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
 
-    for _ in 0..config.concurrency {
-        let session = session.clone();
-        let prepared_insert = prepared_insert.clone();
-        let prepared_select = prepared_select.clone();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
+        Ok(())
+    }
 
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
+    async fn delete(&self, pk: i64) -> Result<()> {
+        self.session
+            .exec_with_params(&self.prepared_delete, self.params(query_values!(pk)))
+            .await?;
 
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+        Ok(())
+    }
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
-
-                for pk in cur_batch_start..cur_batch_end {
-                    if config.workload == Workload::Inserts || config.workload == Workload::Mixed {
-                        session
-                            .exec_with_values(&prepared_insert, query_values!(pk, 2 * pk, 3 * pk))
-                            .await
-                            .unwrap();
-                    }
-
-                    if config.workload == Workload::Selects || config.workload == Workload::Mixed {
-                        let first_row = session
-                            .exec_with_values(&prepared_select, query_values!(pk))
-                            .await
-                            .unwrap()
-                            .body()
-                            .unwrap()
-                            .into_rows()
-                            .unwrap()
-                            .into_iter()
-                            .next()
-                            .unwrap();
-                        
-                        let v1: i64 = first_row.get_by_index(0).unwrap().unwrap();
-                        let v2: i64 = first_row.get_by_index(1).unwrap().unwrap();
-                        
-                        assert_eq!((v1, v2), (2 * pk, 3 * pk));
-                    }
+    async fn insert_wide(&self, pk: i64, ck: i64, values: &[Value]) -> Result<()> {
+        let mut cdrs_values: Vec<CdrsValue> = Vec::with_capacity(values.len() + 2);
+        cdrs_values.push(pk.into());
+        cdrs_values.push(ck.into());
+        cdrs_values.extend(values.iter().map(to_cdrs_value));
+
+        self.session
+            .exec_with_params(
+                &self.prepared_insert_wide,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn scan(&self, pk: i64) -> Result<i64> {
+        // The pager of cdrs-tokio can't bind values of a prepared statement, so pages are fetched here
+        let mut paging_state: Option<CBytes> = None;
+        let mut row_count: i64 = 0;
+        loop {
+            let mut builder = self
+                .params_builder(query_values!(pk))
+                .page_size(self.page_size);
+            if let Some(paging_state) = paging_state.take() {
+                builder = builder.paging_state(paging_state);
+            }
+
+            let body = self
+                .session
+                .exec_with_params(&self.prepared_scan, builder.finalize())
+                .await?
+                .body()?;
+            let metadata = body
+                .as_rows_metadata()
+                .ok_or_else(|| anyhow!("Scan didn't return rows"))?;
+            let has_more_pages = RowsMetadataFlag::has_has_more_pages(metadata.flags);
+            paging_state = metadata.paging_state;
+
+            for row in body.into_rows().unwrap_or_default() {
+                for (index, column_type) in self.columns.iter().enumerate() {
+                    get_value(&row, index, *column_type)?;
                 }
+                row_count += 1;
+            }
+
+            if !has_more_pages {
+                break;
             }
-        }));
+        }
+
+        Ok(row_count)
     }
 
-    for handle in handles {
-        handle.await?;
+    async fn batch(&self, inserts: &[BatchInsert]) -> Result<()> {
+        let prepared = match self.same_partition_batches {
+            true => &self.prepared_insert_wide,
+            false => &self.prepared_insert,
+        };
+
+        let mut builder = BatchQueryBuilder::new()
+            .batch_type(self.batch_type.clone())
+            .serial_consistency(self.serial_consistency);
+        if let Some(consistency) = self.consistency {
+            builder = builder.consistency(consistency);
+        }
+
+        for insert in inserts {
+            let mut cdrs_values: Vec<CdrsValue> = Vec::with_capacity(insert.values.len() + 2);
+            cdrs_values.push(insert.pk.into());
+            if let Some(ck) = insert.ck {
+                cdrs_values.push(ck.into());
+            }
+            cdrs_values.extend(insert.values.iter().map(to_cdrs_value));
+
+            builder = builder
+                .add_query_prepared(prepared.clone(), QueryValues::SimpleValues(cdrs_values));
+        }
+
+        self.session.batch_with_params(builder.finalize()?).await?;
+
+        Ok(())
     }
 
-    let bench_time = start_time.elapsed();
-    println!("Finished\n\nBenchmark time: {} ms", bench_time.as_millis());
+    async fn lwt_insert(&self, pk: i64, values: &[Value]) -> Result<bool> {
+        let mut cdrs_values: Vec<CdrsValue> = Vec::with_capacity(values.len() + 1);
+        cdrs_values.push(pk.into());
+        cdrs_values.extend(values.iter().map(to_cdrs_value));
 
-    Ok(())
-}
+        let frame = self
+            .session
+            .exec_with_params(
+                &self.prepared_lwt_insert,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
 
-async fn prepare_keyspace_and_table(session: &Session) -> Result<()> {
-    session.query("DROP KEYSPACE IF EXISTS benchks").await?;
+        applied(frame)
+    }
 
-    tokio::time::sleep(tokio::time::Duration::from_secs(4)).await; // Await schema agreement
+    async fn lwt_update(&self, pk: i64, values: &[Value], expected: &Value) -> Result<bool> {
+        let mut cdrs_values: Vec<CdrsValue> = values.iter().map(to_cdrs_value).collect();
+        cdrs_values.push(pk.into());
+        cdrs_values.push(to_cdrs_value(expected));
 
-    session
-        .query(
-            "CREATE KEYSPACE IF NOT EXISTS benchks WITH REPLICATION = \
-            {'class' : 'SimpleStrategy', 'replication_factor' : 1}",
-        )
-        .await?;
+        let frame = self
+            .session
+            .exec_with_params(
+                &self.prepared_lwt_update,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
 
-    tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        applied(frame)
+    }
 
-    session
-        .query(
-            "CREATE TABLE IF NOT EXISTS benchks.benchtab (pk bigint PRIMARY KEY, v1 bigint, v2 bigint)"
-        )
-        .await?;
-    
-    tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+    async fn counter_update(&self, pk: i64, increment: i64) -> Result<()> {
+        // The increment of a counter is bound as a bigint
+        self.session
+            .exec_with_params(
+                &self.prepared_counter_update,
+                self.params(query_values!(increment, pk)),
+            )
+            .await?;
 
-    Ok(())
-}
+        Ok(())
+    }
 
-async fn prepare_selects_benchmark(
-    session: &Arc<Session>,
-    prepared_insert: &Arc<PreparedQuery>,
-    config: &Arc<Config>,
-) -> Result<()> {
-    println!("Preparing a selects benchmark (inserting values)...");
-
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
-
-    for _ in 0..std::cmp::max(1024, config.concurrency) {
-        let session = session.clone();
-        let prepared_insert = prepared_insert.clone();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
-
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
-
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+    async fn counter_select(&self, pk: i64) -> Result<Option<i64>> {
+        let first_row = self
+            .session
+            .exec_with_params(
+                &self.prepared_counter_select,
+                self.params(query_values!(pk)),
+            )
+            .await?
+            .body()?
+            .into_rows()
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?
+            .into_iter()
+            .next();
+
+        match first_row {
+            Some(row) => {
+                let value: Option<i64> = row.get_by_index(0)?;
+                Ok(Some(
+                    value.ok_or_else(|| error::verification_error("c is null"))?,
+                ))
+            }
+            None => Ok(None),
+        }
+    }
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
+    async fn insert_point(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        ts: i64,
+        values: &[Value],
+    ) -> Result<()> {
+        let mut cdrs_values: Vec<CdrsValue> = Vec::with_capacity(values.len() + 3);
+        cdrs_values.push(sensor_id.into());
+        cdrs_values.push(day.into());
+        // cdrs has no timestamp value, `ts` is bound as milliseconds in an i64
+        cdrs_values.push(ts.into());
+        cdrs_values.extend(values.iter().map(to_cdrs_value));
+
+        self.session
+            .exec_with_params(
+                &self.prepared_insert_point,
+                self.params(QueryValues::SimpleValues(cdrs_values)),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn select_points(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, Vec<Value>)>> {
+        let rows = self
+            .session
+            .exec_with_params(
+                &self.prepared_select_points,
+                self.params(query_values!(sensor_id, day, limit)),
+            )
+            .await?
+            .body()?
+            .into_rows()
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?;
+
+        let mut points = Vec::with_capacity(rows.len());
+        for row in rows {
+            let ts: Option<i64> = row.get_by_index(0)?;
+            let ts = ts.ok_or_else(|| error::verification_error("ts is null"))?;
+
+            // Value columns follow `ts`
+            let values = self
+                .columns
+                .iter()
+                .enumerate()
+                .map(|(index, column_type)| get_value(&row, index + 1, *column_type))
+                .collect::<Result<_>>()?;
+            points.push((ts, values));
+        }
+
+        Ok(points)
+    }
 
-                for pk in cur_batch_start..cur_batch_end {
-                    session
-                        .exec_with_values(&prepared_insert, query_values!(pk, 2 * pk, 3 * pk))
-                        .await
-                        .unwrap();
+    fn classify_error(err: &anyhow::Error) -> ErrorKind {
+        match err.downcast_ref::<CdrsError>() {
+            Some(CdrsError::Server(server_error)) => match server_error.additional_info {
+                AdditionalErrorInfo::ReadTimeout(_) | AdditionalErrorInfo::WriteTimeout(_) => {
+                    ErrorKind::Timeout
                 }
+                AdditionalErrorInfo::Overloaded(_) => ErrorKind::Overloaded,
+                AdditionalErrorInfo::Unavailable(_) => ErrorKind::Unavailable,
+                _ => ErrorKind::Other,
+            },
+            Some(CdrsError::Io(_)) => ErrorKind::Connection,
+            Some(_) => ErrorKind::Other,
+            None => error::classify_generic(err),
+        }
+    }
+}
+
+// Authenticates with the configured credentials, if there are any
+fn authenticator(config: &Config) -> Arc<dyn Authenticator + Send + Sync> {
+    match (&config.user, &config.password) {
+        (Some(user), Some(password)) => Arc::new(StaticPasswordAuthenticator::new(
+            user.as_str(),
+            password.as_str(),
+        )),
+        _ => Arc::new(NoneAuthenticator {}),
+    }
+}
+
+// The transport is a type parameter of the session, so each one is connected separately
+#[async_trait]
+trait Transport: CdrsTransport + Unpin + Send + Sync + 'static {
+    async fn connect(config: &Config) -> Result<Session<Self>>;
+}
+
+#[async_trait]
+impl Transport for TransportTcp {
+    async fn connect(config: &Config) -> Result<Session<Self>> {
+        let mut nodes = Vec::new();
+        for addr in &config.node_addresses {
+            let mut node = NodeTcpConfigBuilder::new(addr, authenticator(config));
+            if let Some(connections) = config.connections_per_host {
+                node = node.max_size(connections).min_idle(Some(connections));
             }
-        }));
+            nodes.push(node.build());
+        }
+
+        let cluster_config = ClusterTcpConfig(nodes);
+        let lb = RoundRobin::new();
+        let retry_policy = Box::new(DefaultRetryPolicy);
+        let session: Session<Self> = match config.compression {
+            Compression::None => new_session(&cluster_config, lb, retry_policy).await?,
+            Compression::Lz4 => new_lz4(&cluster_config, lb, retry_policy).await?,
+            Compression::Snappy => new_snappy(&cluster_config, lb, retry_policy).await?,
+        };
+
+        Ok(session)
+    }
+}
+
+#[async_trait]
+impl Transport for TransportRustls {
+    async fn connect(config: &Config) -> Result<Session<Self>> {
+        let tls_config = Arc::new(tls::tls_config(config)?);
+
+        let mut nodes = Vec::new();
+        for addr in &config.node_addresses {
+            // Unlike the TCP one, the TLS config takes a resolved address
+            let socket_addr = addr
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| anyhow!("Can't resolve node address: {}", addr))?;
+            let mut node = NodeRustlsConfigBuilder::new(
+                socket_addr,
+                tls::dns_name(addr)?,
+                authenticator(config),
+                tls_config.clone(),
+            );
+            if let Some(connections) = config.connections_per_host {
+                node = node.max_size(connections).min_idle(Some(connections));
+            }
+            nodes.push(node.build());
+        }
+
+        let cluster_config = ClusterRustlsConfig(nodes);
+        let lb = RoundRobin::new();
+        let retry_policy = Box::new(DefaultRetryPolicy);
+        let session: Session<Self> = match config.compression {
+            Compression::None => new_tls_session(&cluster_config, lb, retry_policy).await?,
+            Compression::Lz4 => new_lz4_tls(&cluster_config, lb, retry_policy).await?,
+            Compression::Snappy => new_snappy_tls(&cluster_config, lb, retry_policy).await?,
+        };
+
+        Ok(session)
+    }
+}
+
+impl<T: Transport> CdrsDriver<T> {
+    // Parameters of a statement execution with the consistency levels from the configuration
+    fn params(&self, values: QueryValues) -> QueryParams {
+        self.params_builder(values).finalize()
     }
 
-    for handle in handles {
-        handle.await?;
+    fn params_builder(&self, values: QueryValues) -> QueryParamsBuilder {
+        let mut builder = QueryParamsBuilder::new().values(values);
+        if let Some(consistency) = self.consistency {
+            builder = builder.consistency(consistency);
+        }
+        if let Some(serial_consistency) = self.serial_consistency {
+            builder = builder.serial_consistency(serial_consistency);
+        }
+
+        builder
     }
+}
 
-    Ok(())
+fn to_cdrs_consistency(consistency: Consistency) -> CdrsConsistency {
+    match consistency {
+        Consistency::Any => CdrsConsistency::Any,
+        Consistency::One => CdrsConsistency::One,
+        Consistency::Two => CdrsConsistency::Two,
+        Consistency::Three => CdrsConsistency::Three,
+        Consistency::Quorum => CdrsConsistency::Quorum,
+        Consistency::All => CdrsConsistency::All,
+        Consistency::LocalQuorum => CdrsConsistency::LocalQuorum,
+        Consistency::EachQuorum => CdrsConsistency::EachQuorum,
+        Consistency::LocalOne => CdrsConsistency::LocalOne,
+    }
+}
+
+// Serial consistency levels are variants of the same enum in cdrs
+fn to_cdrs_serial_consistency(serial_consistency: SerialConsistency) -> CdrsConsistency {
+    match serial_consistency {
+        SerialConsistency::Serial => CdrsConsistency::Serial,
+        SerialConsistency::LocalSerial => CdrsConsistency::LocalSerial,
+    }
+}
+
+fn to_cdrs_value(value: &Value) -> CdrsValue {
+    match value {
+        Value::Bigint(v) => (*v).into(),
+        Value::Text(v) => v.clone().into(),
+        Value::Blob(v) => Blob::from(v.clone()).into(),
+        Value::Uuid(v) => Uuid::from_bytes(*v).into(),
+        // Milliseconds since the epoch, encoded like a bigint
+        Value::Timestamp(v) => (*v).into(),
+        Value::List(v) => v.clone().into(),
+        // cdrs converts a Vec to a list, its bytes are also a valid set
+        Value::Set(v) => v.clone().into(),
+        Value::Map(v) => v.iter().cloned().collect::<HashMap<_, _>>().into(),
+        // Without the derive macros of a separate crate udts have to be serialized by hand:
+        // each field is an [int] length followed by the bytes of the value
+        Value::Udt { id, name } => {
+            let mut bytes = Vec::with_capacity(16 + name.len());
+            bytes.extend_from_slice(&8i32.to_be_bytes());
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(&(name.len() as i32).to_be_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            CdrsValue::new_normal(Bytes::new(bytes))
+        }
+    }
+}
+
+fn get_value(row: &Row, index: usize, column_type: ColumnType) -> Result<Value> {
+    let value = match column_type {
+        ColumnType::Bigint => row.get_by_index(index)?.map(Value::Bigint),
+        ColumnType::Text => row.get_by_index(index)?.map(Value::Text),
+        ColumnType::Blob => row
+            .get_by_index(index)?
+            .map(|blob: Blob| Value::Blob(blob.into_vec())),
+        ColumnType::Uuid => row
+            .get_by_index(index)?
+            .map(|uuid: Uuid| Value::Uuid(*uuid.as_bytes())),
+        ColumnType::Timestamp => row.get_by_index(index)?.map(Value::Timestamp),
+        ColumnType::List => {
+            let list: Option<List> = row.get_by_index(index)?;
+            match list {
+                Some(list) => list.as_rust_type()?.map(Value::List),
+                None => None,
+            }
+        }
+        ColumnType::Set => {
+            let set: Option<List> = row.get_by_index(index)?;
+            match set {
+                Some(set) => set.as_rust_type()?.map(Value::Set),
+                None => None,
+            }
+        }
+        ColumnType::Map => {
+            let map: Option<Map> = row.get_by_index(index)?;
+            match map {
+                Some(map) => {
+                    let entries: Option<HashMap<String, i64>> = map.as_rust_type()?;
+                    entries.map(|entries| {
+                        let mut entries: Vec<(String, i64)> = entries.into_iter().collect();
+                        entries.sort();
+                        Value::Map(entries)
+                    })
+                }
+                None => None,
+            }
+        }
+        ColumnType::Udt => {
+            let udt: Option<Udt> = row.get_by_index(index)?;
+            match udt {
+                Some(udt) => {
+                    let id: Option<i64> = udt.get_by_name("id")?;
+                    let name: Option<String> = udt.get_by_name("name")?;
+                    id.zip(name).map(|(id, name)| Value::Udt { id, name })
+                }
+                None => None,
+            }
+        }
+    };
+
+    value.ok_or_else(|| error::verification_error(format!("v{} is null", index + 1)))
+}
+
+// The result of a conditional statement is a rows frame with `[applied]` as the first column
+fn applied(frame: Frame) -> Result<bool> {
+    let row = frame
+        .body()?
+        .into_rows()
+        .and_then(|rows| rows.into_iter().next())
+        .ok_or_else(|| anyhow!("Conditional statement didn't return the [applied] column"))?;
+
+    let applied: Option<bool> = row.get_by_index(0)?;
+    applied.ok_or_else(|| anyhow!("[applied] is null"))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // The type of the driver depends on the transport, so the configuration is read first
+    let config = match Config::read(CdrsDriver::<TransportTcp>::DEFAULT_NODES)? {
+        Some(config) => config,
+        None => return Ok(()), // --help only prints usage
+    };
+
+    if config.tls {
+        bench_core::run_with_config::<CdrsDriver<TransportRustls>>(config).await
+    } else {
+        bench_core::run_with_config::<CdrsDriver<TransportTcp>>(config).await
+    }
 }
@@ -0,0 +1,115 @@
+// TLS configuration of the rustls transport
+
+use anyhow::{anyhow, Result};
+use bench_core::config::Config;
+use bench_core::tls::private_key;
+use rustls::internal::pemfile;
+use rustls::{
+    Certificate, ClientConfig, RootCertStore, ServerCertVerified, ServerCertVerifier, TLSError,
+};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use std::time::SystemTime;
+use webpki::{DNSName, DNSNameRef, EndEntityCert, TLSServerTrustAnchors, TrustAnchor};
+
+// Algorithms of certificate signatures supported by rustls
+static SIGNATURE_ALGORITHMS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+// Verifies that the certificate of a node is signed by the CA, but not the name in it,
+// because nodes are usually given by IP addresses, which rustls can't verify.
+// `verify_chain` is false when no CA certificate is given, then any certificate is accepted.
+struct NodeVerifier {
+    verify_chain: bool,
+}
+
+impl ServerCertVerifier for NodeVerifier {
+    fn verify_server_cert(
+        &self,
+        roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> Result<ServerCertVerified, TLSError> {
+        if !self.verify_chain {
+            return Ok(ServerCertVerified::assertion());
+        }
+
+        let (end_entity, intermediates) = presented_certs
+            .split_first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+        let cert = EndEntityCert::from(&end_entity.0).map_err(TLSError::WebPKIError)?;
+        let intermediates: Vec<&[u8]> =
+            intermediates.iter().map(|cert| cert.0.as_slice()).collect();
+        let anchors: Vec<TrustAnchor> = roots
+            .roots
+            .iter()
+            .map(|root| root.to_trust_anchor())
+            .collect();
+        let now = webpki::Time::try_from(SystemTime::now())
+            .map_err(|_| TLSError::FailedToGetCurrentTime)?;
+
+        cert.verify_is_valid_tls_server_cert(
+            SIGNATURE_ALGORITHMS,
+            &TLSServerTrustAnchors(&anchors),
+            &intermediates,
+            now,
+        )
+        .map_err(TLSError::WebPKIError)?;
+
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+pub fn tls_config(config: &Config) -> Result<ClientConfig> {
+    let mut tls_config = ClientConfig::new();
+
+    if let Some(ca_cert) = &config.ca_cert {
+        let mut reader = BufReader::new(File::open(ca_cert)?);
+        let (added, _) = tls_config
+            .root_store
+            .add_pem_file(&mut reader)
+            .map_err(|_| anyhow!("Invalid CA certificate file: {}", ca_cert))?;
+        if added == 0 {
+            return Err(anyhow!("No CA certificate found in {}", ca_cert));
+        }
+    }
+    tls_config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(NodeVerifier {
+            verify_chain: config.ca_cert.is_some(),
+        }));
+
+    if let (Some(client_cert), Some(client_key)) = (&config.client_cert, &config.client_key) {
+        let mut reader = BufReader::new(File::open(client_cert)?);
+        let certs = pemfile::certs(&mut reader)
+            .map_err(|_| anyhow!("Invalid client certificate file: {}", client_cert))?;
+        tls_config.set_single_client_cert(certs, private_key(client_key)?)?;
+    }
+
+    Ok(tls_config)
+}
+
+// Names of the nodes aren't verified (see `NodeVerifier`), so nodes given by IP addresses,
+// which aren't valid DNS names, are just called "localhost"
+pub fn dns_name(addr: &str) -> Result<DNSName> {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    let name = DNSNameRef::try_from_ascii_str(host)
+        .or_else(|_| DNSNameRef::try_from_ascii_str("localhost"))
+        .map_err(|_| anyhow!("Invalid node address: {}", addr))?;
+
+    Ok(name.to_owned())
+}
@@ -0,0 +1,9 @@
+#!/bin/bash
+CONTAINER_TOOL="podman"
+
+if ! [ -x "$(command -v podman)" ]; then
+    CONTAINER_TOOL="docker"
+fi
+
+# The build context is the parent directory, which holds the Cargo workspace of all Rust benchmarks
+$CONTAINER_TOOL build "$@" -f Dockerfile .. -t rust-driver-benchmarks-basic-mock-server
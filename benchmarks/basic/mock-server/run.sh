@@ -0,0 +1,14 @@
+#!/bin/bash
+CONTAINER_TOOL="podman"
+
+if ! [ -x "$(command -v podman)" ]; then
+    CONTAINER_TOOL="docker"
+fi
+
+# Certificates used for TLS have to be in CERTS_DIR, which is mounted at the same path in the container
+VOLUMES=""
+if [ -n "$CERTS_DIR" ]; then
+    VOLUMES="-v $CERTS_DIR:$CERTS_DIR:ro"
+fi
+
+$CONTAINER_TOOL run --rm -it --network host $VOLUMES rust-driver-benchmarks-basic-mock-server /source/mock-server "$@"
@@ -0,0 +1,404 @@
+use crate::cql::{self, Statement};
+use crate::error::CqlError;
+use crate::frame::{
+    opcode, read_frame, write_bytes, write_frame, write_int, write_short, write_short_bytes,
+    write_string, write_string_multimap, BodyReader, Frame, QueryParameters, FLAG_COMPRESSION,
+    FLAG_CUSTOM_PAYLOAD, PROTOCOL_VERSION,
+};
+use crate::store::{resolve_keyspace, ColumnSpec, QueryResult, Rows, Store};
+use crate::system;
+use anyhow::Result;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+
+const RESULT_VOID: i32 = 0x0001;
+const RESULT_ROWS: i32 = 0x0002;
+const RESULT_SET_KEYSPACE: i32 = 0x0003;
+const RESULT_PREPARED: i32 = 0x0004;
+
+const METADATA_GLOBAL_TABLES_SPEC: i32 = 0x0001;
+const METADATA_NO_METADATA: i32 = 0x0004;
+
+const PASSWORD_AUTHENTICATOR: &str = "org.apache.cassandra.auth.PasswordAuthenticator";
+
+struct Prepared {
+    statement: Statement,
+    // Keyspace set with USE at the time of preparing
+    keyspace: Option<String>,
+}
+
+// State shared by all connections
+#[derive(Default)]
+pub struct ServerState {
+    store: Mutex<Store>,
+    prepared: Mutex<HashMap<Vec<u8>, Arc<Prepared>>>,
+    // User and password required from clients, None disables authentication
+    credentials: Option<(String, String)>,
+}
+
+impl ServerState {
+    pub fn new(credentials: Option<(String, String)>) -> ServerState {
+        ServerState {
+            credentials,
+            ..ServerState::default()
+        }
+    }
+}
+
+struct Connection {
+    state: Arc<ServerState>,
+    keyspace: Option<String>,
+    address: IpAddr,
+    authenticated: bool,
+}
+
+pub async fn handle_connection(
+    socket: TcpStream,
+    tls: Option<TlsAcceptor>,
+    state: Arc<ServerState>,
+) -> Result<()> {
+    socket.set_nodelay(true)?;
+    let address = socket.local_addr()?.ip();
+
+    match tls {
+        Some(acceptor) => {
+            let (read_half, write_half) = tokio::io::split(acceptor.accept(socket).await?);
+            serve(read_half, write_half, address, state).await
+        }
+        None => {
+            let (read_half, write_half) = socket.into_split();
+            serve(read_half, write_half, address, state).await
+        }
+    }
+}
+
+async fn serve<R, W>(
+    read_half: R,
+    write_half: W,
+    address: IpAddr,
+    state: Arc<ServerState>,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let authenticated = state.credentials.is_none();
+    let mut connection = Connection {
+        state,
+        keyspace: None,
+        address,
+        authenticated,
+    };
+
+    let mut reader = BufReader::new(read_half);
+    let mut writer = BufWriter::new(write_half);
+    let mut response = Vec::new();
+
+    while let Some(frame) = read_frame(&mut reader).await? {
+        response.clear();
+        connection.handle_frame(&frame, &mut response);
+        writer.write_all(&response).await?;
+
+        // Send responses in bigger chunks when the client pipelines requests
+        if reader.buffer().is_empty() {
+            writer.flush().await?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Connection {
+    fn handle_frame(&mut self, frame: &Frame, out: &mut Vec<u8>) {
+        let mut body = Vec::new();
+
+        let opcode = match self.process(frame, &mut body) {
+            Ok(opcode) => opcode,
+            Err(err) => {
+                body.clear();
+                err.write(&mut body);
+                opcode::ERROR
+            }
+        };
+
+        write_frame(out, frame.stream, opcode, &body);
+    }
+
+    // Writes the response body and returns its opcode
+    fn process(&mut self, frame: &Frame, body: &mut Vec<u8>) -> Result<u8, CqlError> {
+        if frame.version & 0x7F != PROTOCOL_VERSION {
+            return Err(CqlError::protocol(format!(
+                "Invalid or unsupported protocol version ({}); supported versions are (4/v4)",
+                frame.version & 0x7F
+            )));
+        }
+
+        if frame.flags & FLAG_COMPRESSION != 0 {
+            return Err(CqlError::protocol("Compression is not supported"));
+        }
+
+        let mut reader = BodyReader::new(&frame.body);
+        if frame.flags & FLAG_CUSTOM_PAYLOAD != 0 {
+            reader.skip_bytes_map()?;
+        }
+
+        match frame.opcode {
+            opcode::OPTIONS => {
+                write_string_multimap(body, &[("CQL_VERSION", &["3.4.5"]), ("COMPRESSION", &[])]);
+                Ok(opcode::SUPPORTED)
+            }
+            opcode::STARTUP => {
+                let options = reader.read_string_map()?;
+                if options.contains_key("COMPRESSION") {
+                    return Err(CqlError::protocol("Compression is not supported"));
+                }
+                if self.authenticated {
+                    Ok(opcode::READY)
+                } else {
+                    write_string(body, PASSWORD_AUTHENTICATOR);
+                    Ok(opcode::AUTHENTICATE)
+                }
+            }
+            opcode::AUTH_RESPONSE => {
+                let token = reader.read_bytes()?.unwrap_or_default();
+                if !self.check_credentials(&token) {
+                    return Err(CqlError::new(
+                        CqlError::BAD_CREDENTIALS,
+                        "Provided username and/or password are incorrect",
+                    ));
+                }
+                self.authenticated = true;
+                write_bytes(body, None);
+                Ok(opcode::AUTH_SUCCESS)
+            }
+            _ if !self.authenticated => Err(CqlError::protocol(format!(
+                "Unexpected message {:#04x}, the connection is not authenticated",
+                frame.opcode
+            ))),
+            // The mock never sends any events, so registering for them only needs an answer
+            opcode::REGISTER => Ok(opcode::READY),
+            opcode::QUERY => {
+                let query = reader.read_long_string()?;
+                let params = QueryParameters::read(&mut reader)?;
+                let statement = cql::parse(&query)?;
+                let keyspace = self.keyspace.clone();
+
+                let result = self.execute(&statement, keyspace.as_deref(), &params)?;
+                write_result(body, &result, false);
+                Ok(opcode::RESULT)
+            }
+            opcode::PREPARE => {
+                let query = reader.read_long_string()?;
+                self.prepare(&query, body)?;
+                Ok(opcode::RESULT)
+            }
+            opcode::EXECUTE => {
+                let id = reader.read_short_bytes()?;
+                let params = QueryParameters::read(&mut reader)?;
+                let prepared = self.prepared(id)?;
+
+                let result =
+                    self.execute(&prepared.statement, prepared.keyspace.as_deref(), &params)?;
+                write_result(body, &result, params.skip_metadata);
+                Ok(opcode::RESULT)
+            }
+            opcode::BATCH => {
+                let _batch_type = reader.read_byte()?;
+                let count = reader.read_short()?;
+
+                let mut statements = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let prepared = match reader.read_byte()? {
+                        0 => Arc::new(Prepared {
+                            statement: cql::parse(&reader.read_long_string()?)?,
+                            keyspace: self.keyspace.clone(),
+                        }),
+                        1 => self.prepared(reader.read_short_bytes()?)?,
+                        kind => {
+                            return Err(CqlError::protocol(format!(
+                                "Invalid kind of batch statement: {}",
+                                kind
+                            )))
+                        }
+                    };
+
+                    let value_count = reader.read_short()?;
+                    let values = (0..value_count)
+                        .map(|_| reader.read_bytes())
+                        .collect::<Result<Vec<_>>>()?;
+
+                    statements.push((prepared, values));
+                }
+
+                // Statements are applied one by one, the mock doesn't provide atomicity of logged batches
+                for (prepared, values) in statements {
+                    let params = QueryParameters {
+                        values,
+                        skip_metadata: false,
+                    };
+                    self.execute(&prepared.statement, prepared.keyspace.as_deref(), &params)?;
+                }
+
+                write_int(body, RESULT_VOID);
+                Ok(opcode::RESULT)
+            }
+            other => Err(CqlError::protocol(format!(
+                "Unsupported opcode: {:#04x}",
+                other
+            ))),
+        }
+    }
+
+    // PasswordAuthenticator expects a SASL PLAIN token: \0user\0password
+    fn check_credentials(&self, token: &[u8]) -> bool {
+        let (user, password) = match &self.state.credentials {
+            Some(credentials) => credentials,
+            None => return true,
+        };
+
+        let mut parts = token.split(|b| *b == 0);
+        parts.next() == Some(b"")
+            && parts.next() == Some(user.as_bytes())
+            && parts.next() == Some(password.as_bytes())
+            && parts.next().is_none()
+    }
+
+    fn prepared(&self, id: Vec<u8>) -> Result<Arc<Prepared>, CqlError> {
+        self.state
+            .prepared
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| CqlError::unprepared(id))
+    }
+
+    fn execute(
+        &mut self,
+        statement: &Statement,
+        keyspace: Option<&str>,
+        params: &QueryParameters,
+    ) -> Result<QueryResult, CqlError> {
+        if let Statement::Select {
+            table, selection, ..
+        } = statement
+        {
+            let table_keyspace = resolve_keyspace(table, keyspace)?;
+            if system::is_system_keyspace(&table_keyspace) {
+                let rows = system::select(&table_keyspace, &table.table, selection, self.address);
+                return Ok(QueryResult::Rows(rows));
+            }
+        }
+
+        let result =
+            self.state
+                .store
+                .lock()
+                .unwrap()
+                .execute(statement, keyspace, &params.values)?;
+
+        if let QueryResult::SetKeyspace(keyspace) = &result {
+            self.keyspace = Some(keyspace.clone());
+        }
+
+        Ok(result)
+    }
+
+    fn prepare(&mut self, query: &str, body: &mut Vec<u8>) -> Result<(), CqlError> {
+        let statement = cql::parse(query)?;
+        let metadata = self
+            .state
+            .store
+            .lock()
+            .unwrap()
+            .prepare(&statement, self.keyspace.as_deref())?;
+
+        let mut hasher = DefaultHasher::new();
+        (query, &self.keyspace).hash(&mut hasher);
+        let id = hasher.finish().to_be_bytes().to_vec();
+
+        self.state.prepared.lock().unwrap().insert(
+            id.clone(),
+            Arc::new(Prepared {
+                statement,
+                keyspace: self.keyspace.clone(),
+            }),
+        );
+
+        write_int(body, RESULT_PREPARED);
+        write_short_bytes(body, &id);
+
+        write_int(body, METADATA_GLOBAL_TABLES_SPEC);
+        write_int(body, metadata.bind_markers.len() as i32);
+        write_int(body, metadata.pk_indexes.len() as i32);
+        for index in &metadata.pk_indexes {
+            write_short(body, *index);
+        }
+        write_string(body, &metadata.keyspace);
+        write_string(body, &metadata.table);
+        write_column_specs(body, &metadata.bind_markers);
+
+        match &metadata.result_columns {
+            Some(columns) => {
+                write_int(body, METADATA_GLOBAL_TABLES_SPEC);
+                write_int(body, columns.len() as i32);
+                write_string(body, &metadata.keyspace);
+                write_string(body, &metadata.table);
+                write_column_specs(body, columns);
+            }
+            None => {
+                write_int(body, METADATA_NO_METADATA);
+                write_int(body, 0);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_column_specs(out: &mut Vec<u8>, columns: &[ColumnSpec]) {
+    for column in columns {
+        write_string(out, &column.name);
+        column.cql_type.write(out);
+    }
+}
+
+fn write_result(out: &mut Vec<u8>, result: &QueryResult, skip_metadata: bool) {
+    match result {
+        QueryResult::Void => write_int(out, RESULT_VOID),
+        QueryResult::SetKeyspace(keyspace) => {
+            write_int(out, RESULT_SET_KEYSPACE);
+            write_string(out, keyspace);
+        }
+        QueryResult::Rows(rows) => {
+            write_int(out, RESULT_ROWS);
+            write_rows(out, rows, skip_metadata);
+        }
+    }
+}
+
+fn write_rows(out: &mut Vec<u8>, rows: &Rows, skip_metadata: bool) {
+    if skip_metadata {
+        write_int(out, METADATA_NO_METADATA);
+        write_int(out, rows.columns.len() as i32);
+    } else {
+        write_int(out, METADATA_GLOBAL_TABLES_SPEC);
+        write_int(out, rows.columns.len() as i32);
+        write_string(out, &rows.keyspace);
+        write_string(out, &rows.table);
+        write_column_specs(out, &rows.columns);
+    }
+
+    write_int(out, rows.rows.len() as i32);
+    for row in &rows.rows {
+        for value in row {
+            write_bytes(out, value.as_deref());
+        }
+    }
+}
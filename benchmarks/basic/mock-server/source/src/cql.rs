@@ -0,0 +1,1032 @@
+// A small parser for the subset of CQL used by the benchmarks and by drivers during connection setup
+
+use crate::error::CqlError;
+use crate::frame::{write_short, write_string};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CqlType {
+    Native(u16),
+    List(Box<CqlType>),
+    Set(Box<CqlType>),
+    Map(Box<CqlType>, Box<CqlType>),
+    Udt(UserType),
+}
+
+// A user defined type, as parsed it only has a name and no fields until the store resolves it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserType {
+    // Empty means the keyspace of the table
+    pub keyspace: String,
+    pub name: String,
+    pub fields: Vec<(String, CqlType)>,
+}
+
+impl CqlType {
+    pub const BIGINT: CqlType = CqlType::Native(0x0002);
+    pub const BOOLEAN: CqlType = CqlType::Native(0x0004);
+    pub const COUNTER: CqlType = CqlType::Native(0x0005);
+    pub const INT: CqlType = CqlType::Native(0x0009);
+    pub const TIMESTAMP: CqlType = CqlType::Native(0x000B);
+    pub const UUID: CqlType = CqlType::Native(0x000C);
+    pub const VARCHAR: CqlType = CqlType::Native(0x000D);
+    pub const INET: CqlType = CqlType::Native(0x0010);
+
+    pub fn from_name(name: &str, params: Vec<CqlType>) -> Result<CqlType, CqlError> {
+        let mut params = params.into_iter();
+
+        let cql_type = match name {
+            "ascii" => CqlType::Native(0x0001),
+            "bigint" => CqlType::BIGINT,
+            "blob" => CqlType::Native(0x0003),
+            "boolean" => CqlType::BOOLEAN,
+            "counter" => CqlType::COUNTER,
+            "decimal" => CqlType::Native(0x0006),
+            "double" => CqlType::Native(0x0007),
+            "float" => CqlType::Native(0x0008),
+            "int" => CqlType::INT,
+            "timestamp" => CqlType::TIMESTAMP,
+            "uuid" => CqlType::UUID,
+            "text" | "varchar" => CqlType::VARCHAR,
+            "varint" => CqlType::Native(0x000E),
+            "timeuuid" => CqlType::Native(0x000F),
+            "inet" => CqlType::INET,
+            "date" => CqlType::Native(0x0011),
+            "time" => CqlType::Native(0x0012),
+            "smallint" => CqlType::Native(0x0013),
+            "tinyint" => CqlType::Native(0x0014),
+            "frozen" => params.next().ok_or_else(|| invalid_type(name))?,
+            "list" => CqlType::List(Box::new(params.next().ok_or_else(|| invalid_type(name))?)),
+            "set" => CqlType::Set(Box::new(params.next().ok_or_else(|| invalid_type(name))?)),
+            "map" => {
+                let key = params.next().ok_or_else(|| invalid_type(name))?;
+                let value = params.next().ok_or_else(|| invalid_type(name))?;
+                CqlType::Map(Box::new(key), Box::new(value))
+            }
+            other if params.len() == 0 => CqlType::Udt(UserType {
+                keyspace: String::new(),
+                name: other.to_string(),
+                fields: Vec::new(),
+            }),
+            other => return Err(invalid_type(other)),
+        };
+
+        Ok(cql_type)
+    }
+
+    // Writes the type as an [option] used in result metadata
+    pub fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            CqlType::Native(id) => write_short(out, *id),
+            CqlType::List(elem) => {
+                write_short(out, 0x0020);
+                elem.write(out);
+            }
+            CqlType::Map(key, value) => {
+                write_short(out, 0x0021);
+                key.write(out);
+                value.write(out);
+            }
+            CqlType::Set(elem) => {
+                write_short(out, 0x0022);
+                elem.write(out);
+            }
+            CqlType::Udt(user_type) => {
+                write_short(out, 0x0030);
+                write_string(out, &user_type.keyspace);
+                write_string(out, &user_type.name);
+                write_short(out, user_type.fields.len() as u16);
+                for (name, field_type) in &user_type.fields {
+                    write_string(out, name);
+                    field_type.write(out);
+                }
+            }
+        }
+    }
+
+    // Serializes a literal from the query string
+    pub fn encode_literal(&self, literal: &Literal) -> Result<Vec<u8>, CqlError> {
+        let invalid = || CqlError::invalid(format!("Invalid literal {:?}", literal));
+
+        match (self, literal) {
+            // Counters and timestamps are 8 byte big-endian integers like bigints
+            (&CqlType::BIGINT, Literal::Number(n))
+            | (&CqlType::COUNTER, Literal::Number(n))
+            | (&CqlType::TIMESTAMP, Literal::Number(n)) => Ok(n
+                .parse::<i64>()
+                .map_err(|_| invalid())?
+                .to_be_bytes()
+                .to_vec()),
+            (&CqlType::INT, Literal::Number(n)) => Ok(n
+                .parse::<i32>()
+                .map_err(|_| invalid())?
+                .to_be_bytes()
+                .to_vec()),
+            (_, Literal::String(s)) => Ok(s.as_bytes().to_vec()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn invalid_type(name: &str) -> CqlError {
+    CqlError::invalid(format!("Unsupported type: {}", name))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Literal {
+    Number(String),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Term {
+    BindMarker,
+    Literal(Literal),
+}
+
+// Right hand side of `column = ...` in UPDATE
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Assignment {
+    Set(Term),
+    // `c = c + term`, only for counters
+    Add(Term),
+}
+
+impl Assignment {
+    pub fn term(&self) -> &Term {
+        match self {
+            Assignment::Set(term) | Assignment::Add(term) => term,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableName {
+    // None means the keyspace set with USE
+    pub keyspace: Option<String>,
+    pub table: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct ColumnDefinition {
+    pub name: String,
+    pub cql_type: CqlType,
+}
+
+#[derive(Clone, Debug)]
+pub enum Selection {
+    All,
+    Columns(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub enum Statement {
+    Use(String),
+    CreateKeyspace {
+        name: String,
+    },
+    DropKeyspace {
+        name: String,
+        if_exists: bool,
+    },
+    CreateTable {
+        name: TableName,
+        if_not_exists: bool,
+        columns: Vec<ColumnDefinition>,
+        partition_key: Vec<String>,
+        clustering_key: Vec<String>,
+        // Rows of a partition are sorted from the highest clustering key
+        descending: bool,
+    },
+    DropTable {
+        name: TableName,
+        if_exists: bool,
+    },
+    CreateType {
+        name: TableName,
+        if_not_exists: bool,
+        fields: Vec<ColumnDefinition>,
+    },
+    Insert {
+        table: TableName,
+        columns: Vec<String>,
+        values: Vec<Term>,
+        if_not_exists: bool,
+    },
+    Select {
+        table: TableName,
+        selection: Selection,
+        conditions: Vec<(String, Term)>,
+        limit: Option<Term>,
+    },
+    Update {
+        table: TableName,
+        assignments: Vec<(String, Assignment)>,
+        conditions: Vec<(String, Term)>,
+        // Conditions after IF, empty for a regular update
+        if_conditions: Vec<(String, Term)>,
+    },
+    Delete {
+        table: TableName,
+        conditions: Vec<(String, Term)>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    // Unquoted identifiers and keywords are lowercased
+    Identifier(String),
+    QuotedIdentifier(String),
+    String(String),
+    Number(String),
+    Symbol(char),
+}
+
+fn tokenize(query: &str) -> Result<Vec<Token>, CqlError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            tokens.push(Token::Identifier(word.to_lowercase()));
+        } else if c.is_ascii_digit()
+            || (c == '-' && i + 1 < chars.len() && chars[i + 1].is_ascii_digit())
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len()
+                && (chars[i].is_ascii_alphanumeric() || chars[i] == '.' || chars[i] == '-')
+            {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c == '\'' || c == '"' {
+            // Quotes inside are escaped by doubling them
+            let mut value = String::new();
+            i += 1;
+            loop {
+                if i >= chars.len() {
+                    return Err(CqlError::syntax("Unterminated quoted string"));
+                }
+                if chars[i] == c {
+                    if i + 1 < chars.len() && chars[i + 1] == c {
+                        value.push(c);
+                        i += 2;
+                        continue;
+                    }
+                    i += 1;
+                    break;
+                }
+                value.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(if c == '\'' {
+                Token::String(value)
+            } else {
+                Token::QuotedIdentifier(value)
+            });
+        } else {
+            tokens.push(Token::Symbol(c));
+            i += 1;
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+pub fn parse(query: &str) -> Result<Statement, CqlError> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        pos: 0,
+    };
+
+    let statement = parser.parse_statement()?;
+
+    parser.try_symbol(';');
+    if parser.pos != parser.tokens.len() {
+        return Err(CqlError::syntax(format!(
+            "Unexpected input after the end of the statement: {}",
+            query
+        )));
+    }
+
+    Ok(statement)
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, CqlError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| CqlError::syntax("Unexpected end of the statement"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn try_keyword(&mut self, keyword: &str) -> bool {
+        if self.peek() == Some(&Token::Identifier(keyword.to_string())) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), CqlError> {
+        if self.try_keyword(keyword) {
+            return Ok(());
+        }
+        Err(CqlError::syntax(format!(
+            "Expected {}",
+            keyword.to_uppercase()
+        )))
+    }
+
+    fn try_symbol(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn symbol(&mut self, symbol: char) -> Result<(), CqlError> {
+        if self.try_symbol(symbol) {
+            return Ok(());
+        }
+        Err(CqlError::syntax(format!("Expected '{}'", symbol)))
+    }
+
+    fn identifier(&mut self) -> Result<String, CqlError> {
+        match self.next()? {
+            Token::Identifier(name) | Token::QuotedIdentifier(name) => Ok(name),
+            other => Err(CqlError::syntax(format!(
+                "Expected an identifier, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    fn table_name(&mut self) -> Result<TableName, CqlError> {
+        let first = self.identifier()?;
+
+        if self.try_symbol('.') {
+            return Ok(TableName {
+                keyspace: Some(first),
+                table: self.identifier()?,
+            });
+        }
+
+        Ok(TableName {
+            keyspace: None,
+            table: first,
+        })
+    }
+
+    fn if_not_exists(&mut self) -> Result<bool, CqlError> {
+        if self.try_keyword("if") {
+            self.keyword("not")?;
+            self.keyword("exists")?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn if_exists(&mut self) -> Result<bool, CqlError> {
+        if self.try_keyword("if") {
+            self.keyword("exists")?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // Options after WITH are accepted, but ignored
+    fn skip_rest(&mut self) {
+        while let Some(token) = self.peek() {
+            if token == &Token::Symbol(';') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, CqlError> {
+        match self.next()? {
+            Token::Symbol('?') => Ok(Term::BindMarker),
+            Token::Number(n) => Ok(Term::Literal(Literal::Number(n))),
+            Token::String(s) => Ok(Term::Literal(Literal::String(s))),
+            other => Err(CqlError::syntax(format!(
+                "Expected a value, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    fn cql_type(&mut self) -> Result<CqlType, CqlError> {
+        let name = self.identifier()?;
+
+        // Only user defined types can be qualified with a keyspace
+        if self.try_symbol('.') {
+            return Ok(CqlType::Udt(UserType {
+                keyspace: name,
+                name: self.identifier()?,
+                fields: Vec::new(),
+            }));
+        }
+
+        let mut params = Vec::new();
+        if self.try_symbol('<') {
+            loop {
+                params.push(self.cql_type()?);
+                if self.try_symbol('>') {
+                    break;
+                }
+                self.symbol(',')?;
+            }
+        }
+
+        CqlType::from_name(&name, params)
+    }
+
+    fn identifier_list(&mut self) -> Result<Vec<String>, CqlError> {
+        self.symbol('(')?;
+        let mut names = vec![self.identifier()?];
+        while self.try_symbol(',') {
+            names.push(self.identifier()?);
+        }
+        self.symbol(')')?;
+        Ok(names)
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, CqlError> {
+        match self.next()? {
+            Token::Identifier(keyword) if keyword == "use" => {
+                Ok(Statement::Use(self.identifier()?))
+            }
+            Token::Identifier(keyword) if keyword == "create" => self.parse_create(),
+            Token::Identifier(keyword) if keyword == "drop" => self.parse_drop(),
+            Token::Identifier(keyword) if keyword == "insert" => self.parse_insert(),
+            Token::Identifier(keyword) if keyword == "select" => self.parse_select(),
+            Token::Identifier(keyword) if keyword == "update" => self.parse_update(),
+            Token::Identifier(keyword) if keyword == "delete" => self.parse_delete(),
+            other => Err(CqlError::syntax(format!(
+                "Unsupported statement: {:?}",
+                other
+            ))),
+        }
+    }
+
+    fn parse_create(&mut self) -> Result<Statement, CqlError> {
+        if self.try_keyword("keyspace") {
+            self.if_not_exists()?;
+            let name = self.identifier()?;
+            self.skip_rest();
+            return Ok(Statement::CreateKeyspace { name });
+        }
+
+        if self.try_keyword("type") {
+            let if_not_exists = self.if_not_exists()?;
+            let name = self.table_name()?;
+
+            let mut fields = Vec::new();
+            self.symbol('(')?;
+            loop {
+                fields.push(ColumnDefinition {
+                    name: self.identifier()?,
+                    cql_type: self.cql_type()?,
+                });
+                if self.try_symbol(')') {
+                    break;
+                }
+                self.symbol(',')?;
+            }
+
+            return Ok(Statement::CreateType {
+                name,
+                if_not_exists,
+                fields,
+            });
+        }
+
+        self.keyword("table")?;
+        let if_not_exists = self.if_not_exists()?;
+        let name = self.table_name()?;
+
+        let mut columns = Vec::new();
+        let mut partition_key = Vec::new();
+        let mut clustering_key = Vec::new();
+
+        self.symbol('(')?;
+        loop {
+            if self.try_keyword("primary") {
+                self.keyword("key")?;
+                self.symbol('(')?;
+
+                if self.peek() == Some(&Token::Symbol('(')) {
+                    partition_key = self.identifier_list()?;
+                } else {
+                    partition_key = vec![self.identifier()?];
+                }
+
+                while self.try_symbol(',') {
+                    clustering_key.push(self.identifier()?);
+                }
+                self.symbol(')')?;
+            } else {
+                let column_name = self.identifier()?;
+                let cql_type = self.cql_type()?;
+                self.try_keyword("static");
+
+                if self.try_keyword("primary") {
+                    self.keyword("key")?;
+                    partition_key = vec![column_name.clone()];
+                }
+
+                columns.push(ColumnDefinition {
+                    name: column_name,
+                    cql_type,
+                });
+            }
+
+            if self.try_symbol(')') {
+                break;
+            }
+            self.symbol(',')?;
+        }
+        let descending = self.table_options()?;
+
+        if partition_key.is_empty() {
+            return Err(CqlError::invalid("No PRIMARY KEY specified"));
+        }
+
+        Ok(Statement::CreateTable {
+            name,
+            if_not_exists,
+            columns,
+            partition_key,
+            clustering_key,
+            descending,
+        })
+    }
+
+    // Only the clustering order is used from the options after WITH, others are ignored.
+    // Returns whether the clustering order is descending.
+    fn table_options(&mut self) -> Result<bool, CqlError> {
+        let mut orders = Vec::new();
+
+        if self.try_keyword("with") {
+            loop {
+                if self.try_keyword("clustering") {
+                    self.keyword("order")?;
+                    self.keyword("by")?;
+                    self.symbol('(')?;
+                    loop {
+                        self.identifier()?;
+                        if self.try_keyword("desc") {
+                            orders.push(true);
+                        } else {
+                            self.try_keyword("asc");
+                            orders.push(false);
+                        }
+                        if !self.try_symbol(',') {
+                            break;
+                        }
+                    }
+                    self.symbol(')')?;
+                } else {
+                    while let Some(token) = self.peek() {
+                        if token == &Token::Symbol(';')
+                            || token == &Token::Identifier("and".to_string())
+                        {
+                            break;
+                        }
+                        self.pos += 1;
+                    }
+                }
+
+                if !self.try_keyword("and") {
+                    break;
+                }
+            }
+        }
+
+        if orders.iter().any(|desc| *desc) && !orders.iter().all(|desc| *desc) {
+            return Err(CqlError::invalid(
+                "The mock server doesn't support mixed clustering orders",
+            ));
+        }
+
+        Ok(orders.first() == Some(&true))
+    }
+
+    fn parse_drop(&mut self) -> Result<Statement, CqlError> {
+        if self.try_keyword("keyspace") {
+            let if_exists = self.if_exists()?;
+            let name = self.identifier()?;
+            return Ok(Statement::DropKeyspace { name, if_exists });
+        }
+
+        self.keyword("table")?;
+        let if_exists = self.if_exists()?;
+        let name = self.table_name()?;
+        Ok(Statement::DropTable { name, if_exists })
+    }
+
+    fn parse_insert(&mut self) -> Result<Statement, CqlError> {
+        self.keyword("into")?;
+        let table = self.table_name()?;
+        let columns = self.identifier_list()?;
+
+        self.keyword("values")?;
+        self.symbol('(')?;
+        let mut values = vec![self.term()?];
+        while self.try_symbol(',') {
+            values.push(self.term()?);
+        }
+        self.symbol(')')?;
+
+        if columns.len() != values.len() {
+            return Err(CqlError::invalid("Unmatched column names/values"));
+        }
+
+        let if_not_exists = self.if_not_exists()?;
+
+        // TTL and timestamps of written values are accepted, but ignored
+        if self.try_keyword("using") {
+            loop {
+                if !self.try_keyword("ttl") {
+                    self.keyword("timestamp")?;
+                }
+                self.term()?;
+
+                if !self.try_keyword("and") {
+                    break;
+                }
+            }
+        }
+
+        Ok(Statement::Insert {
+            table,
+            columns,
+            values,
+            if_not_exists,
+        })
+    }
+
+    // Selectors other than plain column names (e.g. function calls) are kept as text
+    fn selector(&mut self) -> Result<String, CqlError> {
+        let mut text = String::new();
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                Some(Token::Symbol(',')) if depth == 0 => break,
+                Some(Token::Identifier(word)) if depth == 0 && word == "from" => break,
+                None => break,
+                _ => {}
+            }
+
+            match self.next()? {
+                Token::Symbol(c) => {
+                    if c == '(' {
+                        depth += 1;
+                    } else if c == ')' {
+                        depth -= 1;
+                    }
+                    text.push(c);
+                }
+                Token::Identifier(word) | Token::QuotedIdentifier(word) => text.push_str(&word),
+                Token::String(s) => text.push_str(&format!("'{}'", s)),
+                Token::Number(n) => text.push_str(&n),
+            }
+        }
+
+        if text.is_empty() {
+            return Err(CqlError::syntax("Expected a selector"));
+        }
+
+        Ok(text)
+    }
+
+    fn parse_select(&mut self) -> Result<Statement, CqlError> {
+        let selection = if self.try_symbol('*') {
+            Selection::All
+        } else {
+            let mut columns = vec![self.selector()?];
+            while self.try_symbol(',') {
+                columns.push(self.selector()?);
+            }
+            Selection::Columns(columns)
+        };
+
+        self.keyword("from")?;
+        let table = self.table_name()?;
+
+        let mut conditions = Vec::new();
+        if self.try_keyword("where") {
+            conditions = self.conditions()?;
+        }
+
+        let mut limit = None;
+        if self.try_keyword("limit") {
+            limit = Some(self.term()?);
+        }
+
+        self.try_keyword("allow");
+        self.try_keyword("filtering");
+
+        Ok(Statement::Select {
+            table,
+            selection,
+            conditions,
+            limit,
+        })
+    }
+
+    // Equality restrictions joined with AND, after WHERE
+    fn conditions(&mut self) -> Result<Vec<(String, Term)>, CqlError> {
+        let mut conditions = Vec::new();
+        loop {
+            let column = self.identifier()?;
+            self.symbol('=')?;
+            conditions.push((column, self.term()?));
+
+            if !self.try_keyword("and") {
+                break;
+            }
+        }
+        Ok(conditions)
+    }
+
+    fn parse_update(&mut self) -> Result<Statement, CqlError> {
+        let table = self.table_name()?;
+
+        self.keyword("set")?;
+        let mut assignments = Vec::new();
+        loop {
+            let column = self.identifier()?;
+            self.symbol('=')?;
+            let assignment = self.assignment(&column)?;
+            assignments.push((column, assignment));
+
+            if !self.try_symbol(',') {
+                break;
+            }
+        }
+
+        self.keyword("where")?;
+        let conditions = self.conditions()?;
+
+        let mut if_conditions = Vec::new();
+        if self.try_keyword("if") {
+            if_conditions = self.conditions()?;
+        }
+
+        Ok(Statement::Update {
+            table,
+            assignments,
+            conditions,
+            if_conditions,
+        })
+    }
+
+    fn assignment(&mut self, column: &str) -> Result<Assignment, CqlError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) | Some(Token::QuotedIdentifier(name))
+                if name == column =>
+            {
+                self.pos += 1;
+                self.symbol('+')?;
+                Ok(Assignment::Add(self.term()?))
+            }
+            _ => Ok(Assignment::Set(self.term()?)),
+        }
+    }
+
+    // Only deletion of whole rows or partitions is supported, without a column list
+    fn parse_delete(&mut self) -> Result<Statement, CqlError> {
+        self.keyword("from")?;
+        let table = self.table_name()?;
+
+        self.keyword("where")?;
+        let conditions = self.conditions()?;
+
+        Ok(Statement::Delete { table, conditions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(keyspace: &str, name: &str) -> TableName {
+        TableName {
+            keyspace: Some(keyspace.to_string()),
+            table: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn create_table() {
+        let statement = parse(
+            "CREATE TABLE IF NOT EXISTS ks.t (pk bigint, ck int, v1 frozen<benchudt>, \
+             v2 map<text, frozen<list<int>>>, PRIMARY KEY ((pk), ck)) \
+             WITH CLUSTERING ORDER BY (ck DESC) AND compaction = {'class': 'x'};",
+        )
+        .unwrap();
+
+        match statement {
+            Statement::CreateTable {
+                name,
+                if_not_exists,
+                columns,
+                partition_key,
+                clustering_key,
+                descending,
+            } => {
+                assert_eq!(name, table("ks", "t"));
+                assert!(if_not_exists);
+                assert_eq!(partition_key, vec!["pk"]);
+                assert_eq!(clustering_key, vec!["ck"]);
+                assert!(descending);
+
+                let types: Vec<CqlType> = columns.into_iter().map(|c| c.cql_type).collect();
+                assert_eq!(
+                    types,
+                    vec![
+                        CqlType::BIGINT,
+                        CqlType::INT,
+                        CqlType::Udt(UserType {
+                            keyspace: String::new(),
+                            name: "benchudt".to_string(),
+                            fields: Vec::new(),
+                        }),
+                        CqlType::Map(
+                            Box::new(CqlType::VARCHAR),
+                            Box::new(CqlType::List(Box::new(CqlType::INT)))
+                        ),
+                    ]
+                );
+            }
+            other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn create_table_without_primary_key() {
+        assert!(parse("CREATE TABLE t (pk bigint, v int)").is_err());
+    }
+
+    #[test]
+    fn create_table_with_mixed_clustering_order() {
+        assert!(parse(
+            "CREATE TABLE t (pk int, a int, b int, PRIMARY KEY (pk, a, b)) \
+             WITH CLUSTERING ORDER BY (a ASC, b DESC)"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn insert() {
+        match parse("INSERT INTO ks.t (pk, \"V1\") VALUES (?, 'it''s') IF NOT EXISTS USING TTL 10")
+            .unwrap()
+        {
+            Statement::Insert {
+                table: name,
+                columns,
+                values,
+                if_not_exists,
+            } => {
+                assert_eq!(name, table("ks", "t"));
+                assert_eq!(columns, vec!["pk", "V1"]);
+                assert_eq!(
+                    values,
+                    vec![
+                        Term::BindMarker,
+                        Term::Literal(Literal::String("it's".to_string()))
+                    ]
+                );
+                assert!(if_not_exists);
+            }
+            other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn insert_with_unmatched_values() {
+        assert!(parse("INSERT INTO t (pk, v) VALUES (?)").is_err());
+    }
+
+    #[test]
+    fn select() {
+        match parse("select pk, count(*) from t where pk = ? and ck = -5 limit 10 allow filtering")
+            .unwrap()
+        {
+            Statement::Select {
+                table,
+                selection,
+                conditions,
+                limit,
+            } => {
+                assert_eq!(table.keyspace, None);
+                assert_eq!(table.table, "t");
+                match selection {
+                    Selection::Columns(columns) => assert_eq!(columns, vec!["pk", "count(*)"]),
+                    Selection::All => panic!("Expected a column list"),
+                }
+                assert_eq!(
+                    conditions,
+                    vec![
+                        ("pk".to_string(), Term::BindMarker),
+                        (
+                            "ck".to_string(),
+                            Term::Literal(Literal::Number("-5".to_string()))
+                        ),
+                    ]
+                );
+                assert_eq!(
+                    limit,
+                    Some(Term::Literal(Literal::Number("10".to_string())))
+                );
+            }
+            other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn update() {
+        match parse("UPDATE ks.t SET c = c + ?, v2 = ? WHERE pk = ? IF v1 = ?").unwrap() {
+            Statement::Update {
+                table: name,
+                assignments,
+                conditions,
+                if_conditions,
+            } => {
+                assert_eq!(name, table("ks", "t"));
+                assert_eq!(
+                    assignments,
+                    vec![
+                        ("c".to_string(), Assignment::Add(Term::BindMarker)),
+                        ("v2".to_string(), Assignment::Set(Term::BindMarker)),
+                    ]
+                );
+                assert_eq!(conditions, vec![("pk".to_string(), Term::BindMarker)]);
+                assert_eq!(if_conditions, vec![("v1".to_string(), Term::BindMarker)]);
+            }
+            other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn delete() {
+        match parse("DELETE FROM t WHERE pk = ?;").unwrap() {
+            Statement::Delete { table, conditions } => {
+                assert_eq!(table.table, "t");
+                assert_eq!(conditions, vec![("pk".to_string(), Term::BindMarker)]);
+            }
+            other => panic!("Unexpected statement {:?}", other),
+        }
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(parse("TRUNCATE t").is_err());
+        assert!(parse("SELECT * FROM t WHERE pk = 'unterminated").is_err());
+        assert!(parse("DROP TABLE t extra").is_err());
+        assert!(parse("INSERT INTO t (pk) VALUES (?").is_err());
+    }
+
+    #[test]
+    fn encode_literals() {
+        let number = Literal::Number("5".to_string());
+        assert_eq!(
+            CqlType::BIGINT.encode_literal(&number).unwrap(),
+            5i64.to_be_bytes()
+        );
+        assert_eq!(
+            CqlType::INT.encode_literal(&number).unwrap(),
+            5i32.to_be_bytes()
+        );
+        assert!(CqlType::INT
+            .encode_literal(&Literal::Number("1.5".to_string()))
+            .is_err());
+    }
+}
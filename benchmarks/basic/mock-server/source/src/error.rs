@@ -0,0 +1,61 @@
+use crate::frame::{write_int, write_short_bytes, write_string};
+
+// Error sent back to the client in an ERROR response
+#[derive(Debug)]
+pub struct CqlError {
+    pub code: i32,
+    pub message: String,
+    // Id of the statement, only for Unprepared errors
+    pub unprepared_id: Option<Vec<u8>>,
+}
+
+impl CqlError {
+    pub const PROTOCOL_ERROR: i32 = 0x000A;
+    pub const BAD_CREDENTIALS: i32 = 0x0100;
+    pub const SYNTAX_ERROR: i32 = 0x2000;
+    pub const INVALID: i32 = 0x2200;
+    pub const UNPREPARED: i32 = 0x2500;
+
+    pub fn new(code: i32, message: impl Into<String>) -> CqlError {
+        CqlError {
+            code,
+            message: message.into(),
+            unprepared_id: None,
+        }
+    }
+
+    pub fn protocol(message: impl Into<String>) -> CqlError {
+        CqlError::new(CqlError::PROTOCOL_ERROR, message)
+    }
+
+    pub fn syntax(message: impl Into<String>) -> CqlError {
+        CqlError::new(CqlError::SYNTAX_ERROR, message)
+    }
+
+    pub fn invalid(message: impl Into<String>) -> CqlError {
+        CqlError::new(CqlError::INVALID, message)
+    }
+
+    pub fn unprepared(id: Vec<u8>) -> CqlError {
+        CqlError {
+            code: CqlError::UNPREPARED,
+            message: "Prepared statement not found".to_string(),
+            unprepared_id: Some(id),
+        }
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        write_int(out, self.code);
+        write_string(out, &self.message);
+
+        if let Some(id) = &self.unprepared_id {
+            write_short_bytes(out, id);
+        }
+    }
+}
+
+impl From<anyhow::Error> for CqlError {
+    fn from(err: anyhow::Error) -> CqlError {
+        CqlError::protocol(err.to_string())
+    }
+}
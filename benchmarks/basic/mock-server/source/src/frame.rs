@@ -0,0 +1,250 @@
+// CQL native protocol v4 framing and primitive types
+// https://github.com/apache/cassandra/blob/trunk/doc/native_protocol_v4.spec
+
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub const PROTOCOL_VERSION: u8 = 4;
+pub const RESPONSE_FLAG: u8 = 0x80;
+pub const HEADER_LEN: usize = 9;
+
+pub const FLAG_COMPRESSION: u8 = 0x01;
+pub const FLAG_CUSTOM_PAYLOAD: u8 = 0x04;
+
+pub mod opcode {
+    pub const ERROR: u8 = 0x00;
+    pub const STARTUP: u8 = 0x01;
+    pub const READY: u8 = 0x02;
+    pub const AUTHENTICATE: u8 = 0x03;
+    pub const OPTIONS: u8 = 0x05;
+    pub const SUPPORTED: u8 = 0x06;
+    pub const QUERY: u8 = 0x07;
+    pub const RESULT: u8 = 0x08;
+    pub const PREPARE: u8 = 0x09;
+    pub const EXECUTE: u8 = 0x0A;
+    pub const REGISTER: u8 = 0x0B;
+    pub const BATCH: u8 = 0x0D;
+    pub const AUTH_RESPONSE: u8 = 0x0F;
+    pub const AUTH_SUCCESS: u8 = 0x10;
+}
+
+pub struct Frame {
+    pub version: u8,
+    pub flags: u8,
+    pub stream: i16,
+    pub opcode: u8,
+    pub body: Vec<u8>,
+}
+
+// Returns None when the client closed the connection
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Frame>> {
+    let mut header = [0u8; HEADER_LEN];
+
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let length = i32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+    if length < 0 {
+        return Err(anyhow!("Invalid frame body length: {}", length));
+    }
+
+    let mut body = vec![0u8; length as usize];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(Frame {
+        version: header[0],
+        flags: header[1],
+        stream: i16::from_be_bytes([header[2], header[3]]),
+        opcode: header[4],
+        body,
+    }))
+}
+
+pub fn write_frame(out: &mut Vec<u8>, stream: i16, opcode: u8, body: &[u8]) {
+    out.push(RESPONSE_FLAG | PROTOCOL_VERSION);
+    out.push(0); // flags
+    out.extend_from_slice(&stream.to_be_bytes());
+    out.push(opcode);
+    out.extend_from_slice(&(body.len() as i32).to_be_bytes());
+    out.extend_from_slice(body);
+}
+
+// Reads primitive protocol types from a request body
+pub struct BodyReader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> BodyReader<'a> {
+    pub fn new(buf: &'a [u8]) -> BodyReader<'a> {
+        BodyReader { buf }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(anyhow!("Request body is too short"));
+        }
+
+        let (taken, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(taken)
+    }
+
+    pub fn read_byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_short(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_int(&mut self) -> Result<i32> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_long(&mut self) -> Result<i64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    pub fn read_string(&mut self) -> Result<String> {
+        let len = self.read_short()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+
+    pub fn read_long_string(&mut self) -> Result<String> {
+        let len = self.read_int()?;
+        if len < 0 {
+            return Err(anyhow!("Invalid long string length: {}", len));
+        }
+        Ok(String::from_utf8(self.take(len as usize)?.to_vec())?)
+    }
+
+    pub fn read_short_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_short()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    // Negative length means null, -2 (unset) is treated as null as well
+    pub fn read_bytes(&mut self) -> Result<Option<Vec<u8>>> {
+        let len = self.read_int()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.take(len as usize)?.to_vec()))
+    }
+
+    pub fn read_string_map(&mut self) -> Result<HashMap<String, String>> {
+        let count = self.read_short()?;
+        let mut map = HashMap::new();
+        for _ in 0..count {
+            let key = self.read_string()?;
+            let value = self.read_string()?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+
+    pub fn skip_bytes_map(&mut self) -> Result<()> {
+        let count = self.read_short()?;
+        for _ in 0..count {
+            self.read_string()?;
+            self.read_bytes()?;
+        }
+        Ok(())
+    }
+}
+
+pub fn write_short(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_int(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_short(out, value.len() as u16);
+    out.extend_from_slice(value.as_bytes());
+}
+
+pub fn write_short_bytes(out: &mut Vec<u8>, value: &[u8]) {
+    write_short(out, value.len() as u16);
+    out.extend_from_slice(value);
+}
+
+pub fn write_bytes(out: &mut Vec<u8>, value: Option<&[u8]>) {
+    match value {
+        Some(bytes) => {
+            write_int(out, bytes.len() as i32);
+            out.extend_from_slice(bytes);
+        }
+        None => write_int(out, -1),
+    }
+}
+
+pub fn write_string_multimap(out: &mut Vec<u8>, map: &[(&str, &[&str])]) {
+    write_short(out, map.len() as u16);
+    for (key, values) in map {
+        write_string(out, key);
+        write_short(out, values.len() as u16);
+        for value in values.iter() {
+            write_string(out, value);
+        }
+    }
+}
+
+// Parameters shared by QUERY and EXECUTE requests
+pub struct QueryParameters {
+    pub values: Vec<Option<Vec<u8>>>,
+    pub skip_metadata: bool,
+}
+
+impl QueryParameters {
+    pub fn read(reader: &mut BodyReader) -> Result<QueryParameters> {
+        let _consistency = reader.read_short()?;
+        let flags = reader.read_byte()?;
+
+        let mut values = Vec::new();
+        if flags & 0x01 != 0 {
+            let count = reader.read_short()?;
+            for _ in 0..count {
+                if flags & 0x40 != 0 {
+                    // Values are named, the mock binds them by position anyway
+                    reader.read_string()?;
+                }
+                values.push(reader.read_bytes()?);
+            }
+        }
+
+        let skip_metadata = flags & 0x02 != 0;
+
+        if flags & 0x04 != 0 {
+            let _page_size = reader.read_int()?;
+        }
+        if flags & 0x08 != 0 {
+            let _paging_state = reader.read_bytes()?;
+        }
+        if flags & 0x10 != 0 {
+            let _serial_consistency = reader.read_short()?;
+        }
+        if flags & 0x20 != 0 {
+            let _timestamp = reader.read_long()?;
+        }
+
+        Ok(QueryParameters {
+            values,
+            skip_metadata,
+        })
+    }
+}
@@ -0,0 +1,108 @@
+// A stand-in for a Scylla node which speaks CQL native protocol v4 and keeps rows in memory.
+// It understands just enough CQL to run the basic benchmarks without a real cluster.
+
+mod connection;
+mod cql;
+mod error;
+mod frame;
+mod store;
+mod system;
+mod tls;
+
+use anyhow::{anyhow, Result};
+use connection::{handle_connection, ServerState};
+use getopts::Options;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut opts = Options::new();
+
+    opts.optflag("h", "help", "Print usage information");
+    opts.optopt(
+        "a",
+        "address",
+        "Address to listen on
+        (default: '127.0.0.1:9042')",
+        "ADDRESS",
+    );
+    opts.optopt(
+        "",
+        "tls-cert",
+        "PEM file with the certificate of the server, enables TLS, requires --tls-key",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "tls-key",
+        "PEM file with the private key of the server certificate",
+        "PATH",
+    );
+    opts.optopt(
+        "",
+        "tls-client-ca",
+        "PEM file with a CA certificate, clients have to present a certificate signed by it",
+        "PATH",
+    );
+
+    opts.optopt(
+        "",
+        "user",
+        "Require clients to authenticate as this user, requires --password",
+        "USER",
+    );
+    opts.optopt("", "password", "Password of the user", "PASSWORD");
+
+    let args: Vec<String> = std::env::args().collect();
+    let parsed = opts.parse(&args[1..])?;
+
+    if parsed.opt_present("help") {
+        println!("{}", opts.usage("Mock CQL server"));
+        return Ok(());
+    }
+
+    let address: String = parsed.opt_get_default("address", "127.0.0.1:9042".to_string())?;
+
+    let tls: Option<TlsAcceptor> = match (parsed.opt_str("tls-cert"), parsed.opt_str("tls-key")) {
+        (Some(cert), Some(key)) => Some(tls::acceptor(
+            &cert,
+            &key,
+            parsed.opt_str("tls-client-ca").as_deref(),
+        )?),
+        (None, None) if !parsed.opt_present("tls-client-ca") => None,
+        _ => {
+            return Err(anyhow!(
+                "--tls-cert and --tls-key have to be used together, --tls-client-ca requires them"
+            ))
+        }
+    };
+
+    let credentials = match (parsed.opt_str("user"), parsed.opt_str("password")) {
+        (Some(user), Some(password)) => Some((user, password)),
+        (None, None) => None,
+        _ => return Err(anyhow!("--user and --password have to be used together")),
+    };
+
+    let listener = TcpListener::bind(&address).await?;
+    let state = Arc::new(ServerState::new(credentials));
+
+    println!(
+        "Mock CQL server listening on {}{}",
+        listener.local_addr()?,
+        if tls.is_some() { " (TLS)" } else { "" }
+    );
+
+    loop {
+        let (socket, peer) = listener.accept().await?;
+        let state = state.clone();
+        let tls = tls.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(socket, tls, state).await {
+                eprintln!("Connection from {} failed: {}", peer, err);
+            }
+        });
+    }
+}
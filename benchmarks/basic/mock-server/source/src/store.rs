@@ -0,0 +1,882 @@
+// In-memory storage of user tables and execution of statements against them
+
+use crate::cql::{
+    Assignment, ColumnDefinition, CqlType, Selection, Statement, TableName, Term, UserType,
+};
+use crate::error::CqlError;
+use crate::frame::{write_bytes, write_int, BodyReader};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryInto;
+
+pub type Value = Option<Vec<u8>>;
+
+// Values of primary key columns, which can't be null
+type Key = Vec<Vec<u8>>;
+
+#[derive(Clone, Debug)]
+pub struct ColumnSpec {
+    pub name: String,
+    pub cql_type: CqlType,
+}
+
+pub struct Rows {
+    pub keyspace: String,
+    pub table: String,
+    pub columns: Vec<ColumnSpec>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+pub enum QueryResult {
+    Void,
+    SetKeyspace(String),
+    Rows(Rows),
+}
+
+// Bind markers and result columns of a statement, sent in response to PREPARE
+pub struct PreparedMetadata {
+    pub keyspace: String,
+    pub table: String,
+    pub bind_markers: Vec<ColumnSpec>,
+    pub pk_indexes: Vec<u16>,
+    pub result_columns: Option<Vec<ColumnSpec>>,
+}
+
+struct Table {
+    columns: Vec<ColumnDefinition>,
+    partition_key: Vec<usize>,
+    clustering_key: Vec<usize>,
+    descending: bool,
+    // partition key -> clustering key -> all column values
+    partitions: HashMap<Key, BTreeMap<Key, Vec<Value>>>,
+}
+
+impl Table {
+    fn column_index(&self, name: &str) -> Result<usize, CqlError> {
+        self.columns
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| CqlError::invalid(format!("Undefined column name {}", name)))
+    }
+
+    fn column_spec(&self, index: usize) -> ColumnSpec {
+        ColumnSpec {
+            name: self.columns[index].name.clone(),
+            cql_type: self.columns[index].cql_type.clone(),
+        }
+    }
+
+    fn key(&self, indexes: &[usize], row: &[Value]) -> Result<Key, CqlError> {
+        indexes
+            .iter()
+            .map(|i| {
+                row[*i].clone().ok_or_else(|| {
+                    CqlError::invalid(format!(
+                        "Invalid null value for primary key column {}",
+                        self.columns[*i].name
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    fn row(&self, partition_key: &Key, clustering_key: &Key) -> Option<&Vec<Value>> {
+        self.partitions.get(partition_key)?.get(clustering_key)
+    }
+
+    // Rows of the partition in the clustering order of the table
+    fn clustered_rows<'a>(
+        &self,
+        partition: &'a BTreeMap<Key, Vec<Value>>,
+    ) -> Box<dyn Iterator<Item = &'a Vec<Value>> + 'a> {
+        if self.descending {
+            Box::new(partition.values().rev())
+        } else {
+            Box::new(partition.values())
+        }
+    }
+
+    fn selected_columns(&self, selection: &Selection) -> Result<Vec<usize>, CqlError> {
+        match selection {
+            Selection::All => Ok((0..self.columns.len()).collect()),
+            Selection::Columns(names) => names.iter().map(|n| self.column_index(n)).collect(),
+        }
+    }
+}
+
+// Binds values to bind markers in the order in which they appear in the statement
+struct BoundValues<'a> {
+    values: std::slice::Iter<'a, Value>,
+}
+
+impl<'a> BoundValues<'a> {
+    fn resolve(&mut self, term: &Term, cql_type: &CqlType) -> Result<Value, CqlError> {
+        match term {
+            Term::BindMarker => self
+                .values
+                .next()
+                .cloned()
+                .ok_or_else(|| CqlError::invalid("Not enough values for bind markers")),
+            Term::Literal(literal) => Ok(Some(cql_type.encode_literal(literal)?)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Store {
+    keyspaces: HashSet<String>,
+    tables: HashMap<(String, String), Table>,
+    types: HashMap<(String, String), UserType>,
+}
+
+pub fn resolve_keyspace(name: &TableName, current: Option<&str>) -> Result<String, CqlError> {
+    match (&name.keyspace, current) {
+        (Some(keyspace), _) => Ok(keyspace.clone()),
+        (None, Some(keyspace)) => Ok(keyspace.to_string()),
+        (None, None) => Err(CqlError::invalid(
+            "No keyspace has been specified. USE a keyspace, or explicitly specify keyspace.tablename",
+        )),
+    }
+}
+
+impl Store {
+    fn table(&self, name: &TableName, current_keyspace: Option<&str>) -> Result<&Table, CqlError> {
+        let keyspace = resolve_keyspace(name, current_keyspace)?;
+        self.tables
+            .get(&(keyspace.clone(), name.table.clone()))
+            .ok_or_else(|| {
+                CqlError::invalid(format!("unconfigured table {}.{}", keyspace, name.table))
+            })
+    }
+
+    // Replaces references to user defined types with their definitions
+    fn resolve_type(&self, keyspace: &str, cql_type: &CqlType) -> Result<CqlType, CqlError> {
+        let resolved = match cql_type {
+            CqlType::Native(_) => cql_type.clone(),
+            CqlType::List(elem) => CqlType::List(Box::new(self.resolve_type(keyspace, elem)?)),
+            CqlType::Set(elem) => CqlType::Set(Box::new(self.resolve_type(keyspace, elem)?)),
+            CqlType::Map(key, value) => CqlType::Map(
+                Box::new(self.resolve_type(keyspace, key)?),
+                Box::new(self.resolve_type(keyspace, value)?),
+            ),
+            CqlType::Udt(user_type) => {
+                let keyspace = match user_type.keyspace.as_str() {
+                    "" => keyspace,
+                    other => other,
+                };
+                let key = (keyspace.to_string(), user_type.name.clone());
+                let defined = self.types.get(&key).ok_or_else(|| {
+                    CqlError::invalid(format!("Unknown type {}.{}", key.0, key.1))
+                })?;
+                CqlType::Udt(defined.clone())
+            }
+        };
+
+        Ok(resolved)
+    }
+
+    fn table_mut(
+        &mut self,
+        name: &TableName,
+        current_keyspace: Option<&str>,
+    ) -> Result<&mut Table, CqlError> {
+        let keyspace = resolve_keyspace(name, current_keyspace)?;
+        self.tables
+            .get_mut(&(keyspace.clone(), name.table.clone()))
+            .ok_or_else(|| {
+                CqlError::invalid(format!("unconfigured table {}.{}", keyspace, name.table))
+            })
+    }
+
+    pub fn prepare(
+        &self,
+        statement: &Statement,
+        current_keyspace: Option<&str>,
+    ) -> Result<PreparedMetadata, CqlError> {
+        match statement {
+            Statement::Insert {
+                table: table_name,
+                columns,
+                values,
+                if_not_exists,
+            } => {
+                let table = self.table(table_name, current_keyspace)?;
+                let mut metadata = PreparedMetadata {
+                    keyspace: resolve_keyspace(table_name, current_keyspace)?,
+                    table: table_name.table.clone(),
+                    bind_markers: Vec::new(),
+                    pk_indexes: Vec::new(),
+                    result_columns: None,
+                };
+
+                let mut marker_columns = Vec::new();
+                for (column, value) in columns.iter().zip(values.iter()) {
+                    if *value == Term::BindMarker {
+                        let index = table.column_index(column)?;
+                        marker_columns.push(index);
+                        metadata.bind_markers.push(table.column_spec(index));
+                    }
+                }
+
+                metadata.pk_indexes = pk_indexes(&table.partition_key, &marker_columns);
+                if *if_not_exists {
+                    metadata.result_columns = Some(vec![applied_column()]);
+                }
+                Ok(metadata)
+            }
+            Statement::Select {
+                table: table_name,
+                selection,
+                conditions,
+                limit,
+            } => {
+                let table = self.table(table_name, current_keyspace)?;
+                let mut metadata = PreparedMetadata {
+                    keyspace: resolve_keyspace(table_name, current_keyspace)?,
+                    table: table_name.table.clone(),
+                    bind_markers: Vec::new(),
+                    pk_indexes: Vec::new(),
+                    result_columns: None,
+                };
+
+                let mut marker_columns = Vec::new();
+                for (column, value) in conditions.iter() {
+                    if *value == Term::BindMarker {
+                        let index = table.column_index(column)?;
+                        marker_columns.push(index);
+                        metadata.bind_markers.push(table.column_spec(index));
+                    }
+                }
+
+                if limit == &Some(Term::BindMarker) {
+                    metadata.bind_markers.push(ColumnSpec {
+                        name: "[limit]".to_string(),
+                        cql_type: CqlType::INT,
+                    });
+                }
+
+                metadata.pk_indexes = pk_indexes(&table.partition_key, &marker_columns);
+
+                let selected = table.selected_columns(selection)?;
+                metadata.result_columns =
+                    Some(selected.iter().map(|i| table.column_spec(*i)).collect());
+
+                Ok(metadata)
+            }
+            Statement::Update {
+                table: table_name,
+                assignments,
+                conditions,
+                if_conditions,
+            } => {
+                let table = self.table(table_name, current_keyspace)?;
+                let mut metadata = PreparedMetadata {
+                    keyspace: resolve_keyspace(table_name, current_keyspace)?,
+                    table: table_name.table.clone(),
+                    bind_markers: Vec::new(),
+                    pk_indexes: Vec::new(),
+                    result_columns: None,
+                };
+
+                // Markers of SET come before the markers of WHERE and IF
+                let mut marker_columns = Vec::new();
+                let terms = assignments
+                    .iter()
+                    .map(|(column, assignment)| (column, assignment.term()))
+                    .chain(conditions.iter().map(|(column, term)| (column, term)))
+                    .chain(if_conditions.iter().map(|(column, term)| (column, term)));
+                for (column, value) in terms {
+                    if *value == Term::BindMarker {
+                        let index = table.column_index(column)?;
+                        marker_columns.push(index);
+                        metadata.bind_markers.push(table.column_spec(index));
+                    }
+                }
+
+                metadata.pk_indexes = pk_indexes(&table.partition_key, &marker_columns);
+                if !if_conditions.is_empty() {
+                    metadata.result_columns = Some(vec![applied_column()]);
+                }
+                Ok(metadata)
+            }
+            Statement::Delete {
+                table: table_name,
+                conditions,
+            } => {
+                let table = self.table(table_name, current_keyspace)?;
+                let mut metadata = PreparedMetadata {
+                    keyspace: resolve_keyspace(table_name, current_keyspace)?,
+                    table: table_name.table.clone(),
+                    bind_markers: Vec::new(),
+                    pk_indexes: Vec::new(),
+                    result_columns: None,
+                };
+
+                let mut marker_columns = Vec::new();
+                for (column, value) in conditions.iter() {
+                    if *value == Term::BindMarker {
+                        let index = table.column_index(column)?;
+                        marker_columns.push(index);
+                        metadata.bind_markers.push(table.column_spec(index));
+                    }
+                }
+
+                metadata.pk_indexes = pk_indexes(&table.partition_key, &marker_columns);
+                Ok(metadata)
+            }
+            _ => Err(CqlError::invalid(
+                "The mock server can only prepare INSERT, SELECT, UPDATE and DELETE statements",
+            )),
+        }
+    }
+
+    pub fn execute(
+        &mut self,
+        statement: &Statement,
+        current_keyspace: Option<&str>,
+        values: &[Value],
+    ) -> Result<QueryResult, CqlError> {
+        let mut values = BoundValues {
+            values: values.iter(),
+        };
+
+        match statement {
+            Statement::Use(keyspace) => {
+                if !self.keyspaces.contains(keyspace) {
+                    return Err(CqlError::invalid(format!(
+                        "Keyspace '{}' does not exist",
+                        keyspace
+                    )));
+                }
+                Ok(QueryResult::SetKeyspace(keyspace.clone()))
+            }
+            Statement::CreateKeyspace { name } => {
+                self.keyspaces.insert(name.clone());
+                Ok(QueryResult::Void)
+            }
+            Statement::DropKeyspace { name, if_exists } => {
+                if !self.keyspaces.remove(name) && !if_exists {
+                    return Err(CqlError::invalid(format!(
+                        "Cannot drop non existing keyspace '{}'",
+                        name
+                    )));
+                }
+                self.tables.retain(|(keyspace, _), _| keyspace != name);
+                self.types.retain(|(keyspace, _), _| keyspace != name);
+                Ok(QueryResult::Void)
+            }
+            Statement::CreateTable {
+                name,
+                if_not_exists,
+                columns,
+                partition_key,
+                clustering_key,
+                descending,
+            } => {
+                let keyspace = resolve_keyspace(name, current_keyspace)?;
+                if !self.keyspaces.contains(&keyspace) {
+                    return Err(CqlError::invalid(format!(
+                        "Keyspace {} does not exist",
+                        keyspace
+                    )));
+                }
+
+                let key = (keyspace, name.table.clone());
+                if self.tables.contains_key(&key) {
+                    if *if_not_exists {
+                        return Ok(QueryResult::Void);
+                    }
+                    return Err(CqlError::invalid(format!(
+                        "Table {}.{} already exists",
+                        key.0, key.1
+                    )));
+                }
+
+                let columns = columns
+                    .iter()
+                    .map(|column| {
+                        Ok(ColumnDefinition {
+                            name: column.name.clone(),
+                            cql_type: self.resolve_type(&key.0, &column.cql_type)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, CqlError>>()?;
+
+                let mut table = Table {
+                    columns,
+                    partition_key: Vec::new(),
+                    clustering_key: Vec::new(),
+                    descending: *descending,
+                    partitions: HashMap::new(),
+                };
+                for column in partition_key {
+                    let index = table.column_index(column)?;
+                    table.partition_key.push(index);
+                }
+                for column in clustering_key {
+                    let index = table.column_index(column)?;
+                    table.clustering_key.push(index);
+                }
+
+                self.tables.insert(key, table);
+                Ok(QueryResult::Void)
+            }
+            Statement::CreateType {
+                name,
+                if_not_exists,
+                fields,
+            } => {
+                let keyspace = resolve_keyspace(name, current_keyspace)?;
+                if !self.keyspaces.contains(&keyspace) {
+                    return Err(CqlError::invalid(format!(
+                        "Keyspace {} does not exist",
+                        keyspace
+                    )));
+                }
+
+                let key = (keyspace, name.table.clone());
+                if self.types.contains_key(&key) {
+                    if *if_not_exists {
+                        return Ok(QueryResult::Void);
+                    }
+                    return Err(CqlError::invalid(format!(
+                        "A user type of name {}.{} already exists",
+                        key.0, key.1
+                    )));
+                }
+
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        Ok((
+                            field.name.clone(),
+                            self.resolve_type(&key.0, &field.cql_type)?,
+                        ))
+                    })
+                    .collect::<Result<_, CqlError>>()?;
+
+                let user_type = UserType {
+                    keyspace: key.0.clone(),
+                    name: key.1.clone(),
+                    fields,
+                };
+                self.types.insert(key, user_type);
+                Ok(QueryResult::Void)
+            }
+            Statement::DropTable { name, if_exists } => {
+                let keyspace = resolve_keyspace(name, current_keyspace)?;
+                if self
+                    .tables
+                    .remove(&(keyspace, name.table.clone()))
+                    .is_none()
+                    && !if_exists
+                {
+                    return Err(CqlError::invalid(format!(
+                        "Table {} does not exist",
+                        name.table
+                    )));
+                }
+                Ok(QueryResult::Void)
+            }
+            Statement::Insert {
+                table: table_name,
+                columns,
+                values: terms,
+                if_not_exists,
+            } => {
+                let keyspace = resolve_keyspace(table_name, current_keyspace)?;
+                let table = self.table_mut(table_name, current_keyspace)?;
+
+                let mut row: Vec<Value> = vec![None; table.columns.len()];
+                let mut set_columns = Vec::with_capacity(columns.len());
+                for (column, term) in columns.iter().zip(terms.iter()) {
+                    let index = table.column_index(column)?;
+                    row[index] = values.resolve(term, &table.columns[index].cql_type)?;
+                    set_columns.push(index);
+                }
+
+                let partition_key = table.key(&table.partition_key, &row)?;
+                let clustering_key = table.key(&table.clustering_key, &row)?;
+
+                if *if_not_exists && table.row(&partition_key, &clustering_key).is_some() {
+                    return Ok(applied_result(keyspace, &table_name.table, false));
+                }
+
+                let stored_row = table
+                    .partitions
+                    .entry(partition_key)
+                    .or_default()
+                    .entry(clustering_key)
+                    .or_insert_with(|| vec![None; row.len()]);
+
+                // INSERT is an upsert, columns not mentioned in the statement stay untouched
+                for index in set_columns {
+                    stored_row[index] = row[index].take();
+                }
+
+                if *if_not_exists {
+                    return Ok(applied_result(keyspace, &table_name.table, true));
+                }
+                Ok(QueryResult::Void)
+            }
+            Statement::Select {
+                table: table_name,
+                selection,
+                conditions,
+                limit,
+            } => {
+                let keyspace = resolve_keyspace(table_name, current_keyspace)?;
+                let table = self.table(table_name, current_keyspace)?;
+
+                let mut restrictions: Vec<(usize, Value)> = Vec::with_capacity(conditions.len());
+                for (column, term) in conditions {
+                    let index = table.column_index(column)?;
+                    let value = values.resolve(term, &table.columns[index].cql_type)?;
+                    restrictions.push((index, value));
+                }
+
+                let limit: usize = match limit {
+                    Some(term) => match values.resolve(term, &CqlType::INT)? {
+                        Some(bytes) if bytes.len() == 4 => {
+                            i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+                        }
+                        _ => return Err(CqlError::invalid("Invalid LIMIT value")),
+                    },
+                    None => usize::MAX,
+                };
+
+                let selected = table.selected_columns(selection)?;
+                let matches = |row: &Vec<Value>| restrictions.iter().all(|(i, v)| &row[*i] == v);
+
+                // Look up the partition directly if the whole partition key is restricted
+                let partition_key: Option<Key> = table
+                    .partition_key
+                    .iter()
+                    .map(|pk| {
+                        restrictions
+                            .iter()
+                            .find(|(i, _)| i == pk)
+                            .and_then(|(_, v)| v.clone())
+                    })
+                    .collect();
+
+                let candidates: Box<dyn Iterator<Item = &Vec<Value>>> = match partition_key {
+                    Some(key) => Box::new(
+                        table
+                            .partitions
+                            .get(&key)
+                            .into_iter()
+                            .flat_map(|p| table.clustered_rows(p)),
+                    ),
+                    None => Box::new(
+                        table
+                            .partitions
+                            .values()
+                            .flat_map(|p| table.clustered_rows(p)),
+                    ),
+                };
+
+                let rows: Vec<Vec<Value>> = candidates
+                    .filter(|row| matches(row))
+                    .take(limit)
+                    .map(|row| selected.iter().map(|i| row[*i].clone()).collect())
+                    .collect();
+
+                Ok(QueryResult::Rows(Rows {
+                    keyspace,
+                    table: table_name.table.clone(),
+                    columns: selected.iter().map(|i| table.column_spec(*i)).collect(),
+                    rows,
+                }))
+            }
+            Statement::Update {
+                table: table_name,
+                assignments,
+                conditions,
+                if_conditions,
+            } => {
+                let keyspace = resolve_keyspace(table_name, current_keyspace)?;
+                let table = self.table_mut(table_name, current_keyspace)?;
+
+                let mut row: Vec<Value> = vec![None; table.columns.len()];
+                let mut set_columns = Vec::with_capacity(assignments.len());
+                let mut added_columns = Vec::new();
+                for (column, assignment) in assignments {
+                    let index = table.column_index(column)?;
+                    row[index] =
+                        values.resolve(assignment.term(), &table.columns[index].cql_type)?;
+                    match assignment {
+                        Assignment::Set(_) => set_columns.push(index),
+                        Assignment::Add(_) => added_columns.push(index),
+                    }
+                }
+
+                // The whole primary key has to be restricted, there are no range updates
+                let mut restricted = Vec::with_capacity(conditions.len());
+                for (column, term) in conditions {
+                    let index = table.column_index(column)?;
+                    row[index] = values.resolve(term, &table.columns[index].cql_type)?;
+                    restricted.push(index);
+                }
+                let primary_key = table
+                    .partition_key
+                    .iter()
+                    .chain(table.clustering_key.iter());
+                if primary_key.clone().any(|i| !restricted.contains(i))
+                    || restricted.len() != primary_key.count()
+                {
+                    return Err(CqlError::invalid(
+                        "UPDATE has to restrict all primary key columns and nothing else",
+                    ));
+                }
+
+                let mut expected: Vec<(usize, Value)> = Vec::with_capacity(if_conditions.len());
+                for (column, term) in if_conditions {
+                    let index = table.column_index(column)?;
+                    let value = values.resolve(term, &table.columns[index].cql_type)?;
+                    expected.push((index, value));
+                }
+
+                let partition_key = table.key(&table.partition_key, &row)?;
+                let clustering_key = table.key(&table.clustering_key, &row)?;
+
+                // A conditional update of a missing row isn't applied
+                if !expected.is_empty() {
+                    let matches = match table.row(&partition_key, &clustering_key) {
+                        Some(stored_row) => {
+                            let mut matches = true;
+                            for (i, v) in &expected {
+                                let cql_type = &table.columns[*i].cql_type;
+                                if sorted_value(cql_type, &stored_row[*i])?
+                                    != sorted_value(cql_type, v)?
+                                {
+                                    matches = false;
+                                    break;
+                                }
+                            }
+                            matches
+                        }
+                        None => false,
+                    };
+                    if !matches {
+                        return Ok(applied_result(keyspace, &table_name.table, false));
+                    }
+                }
+
+                let stored_row = table
+                    .partitions
+                    .entry(partition_key)
+                    .or_default()
+                    .entry(clustering_key)
+                    .or_insert_with(|| vec![None; row.len()]);
+
+                // Like INSERT, UPDATE is an upsert which also sets the primary key columns
+                for index in set_columns.into_iter().chain(restricted) {
+                    stored_row[index] = row[index].take();
+                }
+                for index in added_columns {
+                    stored_row[index] = add_counter(&stored_row[index], &row[index])?;
+                }
+
+                if !expected.is_empty() {
+                    return Ok(applied_result(keyspace, &table_name.table, true));
+                }
+                Ok(QueryResult::Void)
+            }
+            Statement::Delete {
+                table: table_name,
+                conditions,
+            } => {
+                let table = self.table_mut(table_name, current_keyspace)?;
+
+                let mut restrictions: Vec<(usize, Value)> = Vec::with_capacity(conditions.len());
+                for (column, term) in conditions {
+                    let index = table.column_index(column)?;
+                    let value = values.resolve(term, &table.columns[index].cql_type)?;
+                    restrictions.push((index, value));
+                }
+
+                // Deletes a whole partition, or the rows of it matching the clustering key restrictions
+                let partition_key: Key = table
+                    .partition_key
+                    .iter()
+                    .map(|pk| {
+                        restrictions
+                            .iter()
+                            .find(|(i, _)| i == pk)
+                            .and_then(|(_, v)| v.clone())
+                    })
+                    .collect::<Option<Key>>()
+                    .ok_or_else(|| {
+                        CqlError::invalid("DELETE has to restrict all partition key columns")
+                    })?;
+
+                if let Some(partition) = table.partitions.get_mut(&partition_key) {
+                    partition.retain(|_, row| !restrictions.iter().all(|(i, v)| &row[*i] == v));
+                    if partition.is_empty() {
+                        table.partitions.remove(&partition_key);
+                    }
+                }
+
+                Ok(QueryResult::Void)
+            }
+        }
+    }
+}
+
+// Counters are bigints, a missing counter is 0
+fn add_counter(counter: &Value, increment: &Value) -> Result<Value, CqlError> {
+    let to_i64 = |value: &Value| match value {
+        None => Ok(0),
+        Some(bytes) => bytes
+            .as_slice()
+            .try_into()
+            .map(i64::from_be_bytes)
+            .map_err(|_| CqlError::invalid("Invalid counter value")),
+    };
+
+    let sum = to_i64(counter)?.wrapping_add(to_i64(increment)?);
+    Ok(Some(sum.to_be_bytes().to_vec()))
+}
+
+// Serialized sets and maps keep the order in which the client iterated them, which is random
+// e.g. for a HashMap, so they are compared in conditions with elements sorted by their bytes
+fn sorted_value(cql_type: &CqlType, value: &Value) -> Result<Value, CqlError> {
+    let bytes = match value {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+    let invalid = |e: anyhow::Error| CqlError::invalid(format!("Invalid value: {}", e));
+    let mut reader = BodyReader::new(bytes);
+    let mut out = Vec::with_capacity(bytes.len());
+
+    match cql_type {
+        CqlType::Native(_) => return Ok(value.clone()),
+        CqlType::List(elem) | CqlType::Set(elem) => {
+            let count = reader.read_int().map_err(invalid)?;
+            let mut elements = Vec::new();
+            for _ in 0..count {
+                elements.push(sorted_value(elem, &reader.read_bytes().map_err(invalid)?)?);
+            }
+            if let CqlType::Set(_) = cql_type {
+                elements.sort();
+            }
+
+            write_int(&mut out, count);
+            for element in &elements {
+                write_bytes(&mut out, element.as_deref());
+            }
+        }
+        CqlType::Map(key_type, value_type) => {
+            let count = reader.read_int().map_err(invalid)?;
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                let key = sorted_value(key_type, &reader.read_bytes().map_err(invalid)?)?;
+                let value = sorted_value(value_type, &reader.read_bytes().map_err(invalid)?)?;
+                entries.push((key, value));
+            }
+            entries.sort();
+
+            write_int(&mut out, count);
+            for (key, value) in &entries {
+                write_bytes(&mut out, key.as_deref());
+                write_bytes(&mut out, value.as_deref());
+            }
+        }
+        // Trailing fields can be omitted
+        CqlType::Udt(user_type) => {
+            for (_, field_type) in &user_type.fields {
+                if reader.is_empty() {
+                    break;
+                }
+                let field = sorted_value(field_type, &reader.read_bytes().map_err(invalid)?)?;
+                write_bytes(&mut out, field.as_deref());
+            }
+        }
+    }
+
+    Ok(Some(out))
+}
+
+// Conditional statements return only the `[applied]` column, without the current values of the row
+fn applied_column() -> ColumnSpec {
+    ColumnSpec {
+        name: "[applied]".to_string(),
+        cql_type: CqlType::BOOLEAN,
+    }
+}
+
+fn applied_result(keyspace: String, table: &str, applied: bool) -> QueryResult {
+    QueryResult::Rows(Rows {
+        keyspace,
+        table: table.to_string(),
+        columns: vec![applied_column()],
+        rows: vec![vec![Some(vec![applied as u8])]],
+    })
+}
+
+// Positions of partition key columns among bind markers, empty if some of them aren't bound
+fn pk_indexes(partition_key: &[usize], marker_columns: &[usize]) -> Vec<u16> {
+    let indexes: Option<Vec<u16>> = partition_key
+        .iter()
+        .map(|pk| {
+            marker_columns
+                .iter()
+                .position(|c| c == pk)
+                .map(|p| p as u16)
+        })
+        .collect();
+
+    indexes.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Map entries are given as keys and values in turn
+    fn collection(count: i32, elements: &[&[u8]]) -> Value {
+        let mut out = Vec::new();
+        write_int(&mut out, count);
+        for element in elements {
+            write_bytes(&mut out, Some(element));
+        }
+        Some(out)
+    }
+
+    #[test]
+    fn maps_are_compared_sorted() {
+        let map = CqlType::Map(Box::new(CqlType::VARCHAR), Box::new(CqlType::INT));
+        let a = collection(2, &[b"a", &1i32.to_be_bytes(), b"b", &2i32.to_be_bytes()]);
+        let b = collection(2, &[b"b", &2i32.to_be_bytes(), b"a", &1i32.to_be_bytes()]);
+        let c = collection(2, &[b"b", &1i32.to_be_bytes(), b"a", &2i32.to_be_bytes()]);
+
+        assert_ne!(a, b);
+        assert_eq!(
+            sorted_value(&map, &a).unwrap(),
+            sorted_value(&map, &b).unwrap()
+        );
+        assert_ne!(
+            sorted_value(&map, &a).unwrap(),
+            sorted_value(&map, &c).unwrap()
+        );
+    }
+
+    #[test]
+    fn lists_keep_their_order() {
+        let list = CqlType::List(Box::new(CqlType::VARCHAR));
+        let a = collection(4, &[b"a", b"b", b"c", b"d"]);
+        let b = collection(4, &[b"c", b"d", b"a", b"b"]);
+
+        assert_ne!(
+            sorted_value(&list, &a).unwrap(),
+            sorted_value(&list, &b).unwrap()
+        );
+        assert_eq!(sorted_value(&list, &None).unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_values_are_invalid() {
+        let set = CqlType::Set(Box::new(CqlType::VARCHAR));
+        assert!(sorted_value(&set, &Some(vec![0, 0, 0, 1])).is_err());
+    }
+}
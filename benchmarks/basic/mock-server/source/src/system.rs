@@ -0,0 +1,121 @@
+// Responses to queries on system tables, which drivers use to discover the cluster.
+// The mock presents itself as a single node cluster, all other system tables are empty.
+
+use crate::cql::{CqlType, Selection};
+use crate::store::{ColumnSpec, Rows, Value};
+use std::net::IpAddr;
+
+const HOST_ID: [u8; 16] = [
+    0x6d, 0x6f, 0x63, 0x6b, 0, 0, 0x40, 0, 0x80, 0, 0, 0, 0, 0, 0, 1,
+];
+const SCHEMA_VERSION: [u8; 16] = [
+    0x6d, 0x6f, 0x63, 0x6b, 0, 0, 0x40, 0, 0x80, 0, 0, 0, 0, 0, 0, 2,
+];
+
+pub fn is_system_keyspace(keyspace: &str) -> bool {
+    keyspace.starts_with("system")
+}
+
+fn inet(address: IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+fn text(value: &str) -> Value {
+    Some(value.as_bytes().to_vec())
+}
+
+fn text_set(values: &[&str]) -> Value {
+    let mut bytes = (values.len() as i32).to_be_bytes().to_vec();
+    for value in values {
+        bytes.extend_from_slice(&(value.len() as i32).to_be_bytes());
+        bytes.extend_from_slice(value.as_bytes());
+    }
+    Some(bytes)
+}
+
+fn local_columns(address: IpAddr) -> Vec<(&'static str, CqlType, Value)> {
+    vec![
+        ("key", CqlType::VARCHAR, text("local")),
+        ("bootstrapped", CqlType::VARCHAR, text("COMPLETED")),
+        ("broadcast_address", CqlType::INET, Some(inet(address))),
+        ("cluster_name", CqlType::VARCHAR, text("Mock Cluster")),
+        ("cql_version", CqlType::VARCHAR, text("3.4.5")),
+        ("data_center", CqlType::VARCHAR, text("datacenter1")),
+        ("host_id", CqlType::UUID, Some(HOST_ID.to_vec())),
+        ("listen_address", CqlType::INET, Some(inet(address))),
+        ("native_protocol_version", CqlType::VARCHAR, text("4")),
+        (
+            "partitioner",
+            CqlType::VARCHAR,
+            text("org.apache.cassandra.dht.Murmur3Partitioner"),
+        ),
+        ("rack", CqlType::VARCHAR, text("rack1")),
+        ("release_version", CqlType::VARCHAR, text("3.0.8")),
+        ("rpc_address", CqlType::INET, Some(inet(address))),
+        (
+            "schema_version",
+            CqlType::UUID,
+            Some(SCHEMA_VERSION.to_vec()),
+        ),
+        (
+            "tokens",
+            CqlType::Set(Box::new(CqlType::VARCHAR)),
+            text_set(&["0"]),
+        ),
+    ]
+}
+
+fn peers_columns() -> Vec<(&'static str, CqlType, Value)> {
+    vec![
+        ("peer", CqlType::INET, None),
+        ("data_center", CqlType::VARCHAR, None),
+        ("host_id", CqlType::UUID, None),
+        ("preferred_ip", CqlType::INET, None),
+        ("rack", CqlType::VARCHAR, None),
+        ("release_version", CqlType::VARCHAR, None),
+        ("rpc_address", CqlType::INET, None),
+        ("schema_version", CqlType::UUID, None),
+        ("tokens", CqlType::Set(Box::new(CqlType::VARCHAR)), None),
+    ]
+}
+
+// `address` is the address of the node which the client connected to
+pub fn select(keyspace: &str, table: &str, selection: &Selection, address: IpAddr) -> Rows {
+    let (known_columns, has_row) = match (keyspace, table) {
+        ("system", "local") => (local_columns(address), true),
+        ("system", "peers") => (peers_columns(), false),
+        _ => (Vec::new(), false),
+    };
+
+    let columns: Vec<(String, CqlType, Value)> = match selection {
+        Selection::All => known_columns
+            .into_iter()
+            .map(|(name, cql_type, value)| (name.to_string(), cql_type, value))
+            .collect(),
+        Selection::Columns(names) => names
+            .iter()
+            .map(|name| {
+                known_columns
+                    .iter()
+                    .find(|(known, _, _)| known == name)
+                    .map(|(_, cql_type, value)| (name.clone(), cql_type.clone(), value.clone()))
+                    .unwrap_or((name.clone(), CqlType::VARCHAR, None))
+            })
+            .collect(),
+    };
+
+    let row: Vec<Value> = columns.iter().map(|(_, _, value)| value.clone()).collect();
+
+    Rows {
+        keyspace: keyspace.to_string(),
+        table: table.to_string(),
+        columns: columns
+            .into_iter()
+            .map(|(name, cql_type, _)| ColumnSpec { name, cql_type })
+            .collect(),
+        rows: if has_row { vec![row] } else { Vec::new() },
+    }
+}
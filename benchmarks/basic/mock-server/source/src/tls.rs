@@ -0,0 +1,44 @@
+// TLS of client connections, enabled by giving the server a certificate and its private key
+
+use anyhow::{anyhow, Result};
+use bench_core::tls::private_key;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::rustls::internal::pemfile;
+use tokio_rustls::rustls::{
+    AllowAnyAuthenticatedClient, NoClientAuth, RootCertStore, ServerConfig,
+};
+use tokio_rustls::TlsAcceptor;
+
+// With a client CA certificate only clients presenting a certificate signed by it are accepted
+pub fn acceptor(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> Result<TlsAcceptor> {
+    let client_auth = match client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            let (added, _) = roots
+                .add_pem_file(&mut BufReader::new(File::open(path)?))
+                .map_err(|_| anyhow!("Invalid CA certificate file: {}", path))?;
+            if added == 0 {
+                return Err(anyhow!("No CA certificate found in {}", path));
+            }
+            AllowAnyAuthenticatedClient::new(roots)
+        }
+        None => NoClientAuth::new(),
+    };
+
+    let certs = pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| anyhow!("Invalid certificate file: {}", cert_path))?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificate found in {}", cert_path));
+    }
+
+    let mut config = ServerConfig::new(client_auth);
+    config.set_single_cert(certs, private_key(key_path)?)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
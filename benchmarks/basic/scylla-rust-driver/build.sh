@@ -5,4 +5,5 @@ if ! [ -x "$(command -v podman)" ]; then
     CONTAINER_TOOL="docker"
 fi
 
-$CONTAINER_TOOL build "$@" . -t rust-driver-benchmarks-basic-scylla-rust-driver
\ No newline at end of file
+# The build context is the parent directory, which holds the Cargo workspace of all Rust benchmarks
+$CONTAINER_TOOL build "$@" -f Dockerfile .. -t rust-driver-benchmarks-basic-scylla-rust-driver
\ No newline at end of file
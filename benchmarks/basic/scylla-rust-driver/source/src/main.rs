@@ -1,180 +1,610 @@
-mod config;
-
-use anyhow::Result;
-use config::{Config, Workload};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bench_core::config::{
+    BatchType, Compression, Config, Consistency, LoadBalancing, LoadBalancingSettings,
+    SerialConsistency,
+};
+use bench_core::error::{self, ErrorKind};
+use bench_core::schema::{BatchInsert, Value};
+use bench_core::Driver;
+use futures::StreamExt;
+use openssl::ssl::{SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode};
+use scylla::batch::{Batch, BatchType as ScyllaBatchType};
+use scylla::frame::response::result::CqlValue;
+use scylla::frame::value::{Counter, SerializedValues, Timestamp};
 use scylla::prepared_statement::PreparedStatement;
-use scylla::{IntoTypedRows, Session, SessionBuilder};
-use std::convert::TryInto;
-use std::sync::atomic::{AtomicI64, Ordering};
+use scylla::statement::{
+    Consistency as ScyllaConsistency, SerialConsistency as ScyllaSerialConsistency,
+};
+use scylla::transport::errors::{DbError, QueryError};
+use scylla::transport::load_balancing::{
+    DcAwareRoundRobinPolicy, LoadBalancingPolicy, RoundRobinPolicy, TokenAwarePolicy,
+};
+use scylla::transport::session::PoolSize;
+use scylla::transport::Compression as ScyllaCompression;
+use scylla::{IntoUserType, QueryResult, Session, SessionBuilder};
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use uuid::Uuid;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    println!("Starting scylla-rust-driver benchmark\n");
+// Fields in the order of `benchudt`
+#[derive(IntoUserType)]
+struct BenchUdt {
+    id: i64,
+    name: String,
+}
+
+struct ScyllaDriver {
+    session: Session,
+    prepared_insert: PreparedStatement,
+    prepared_select: PreparedStatement,
+    prepared_update: PreparedStatement,
+    prepared_delete: PreparedStatement,
+    prepared_insert_wide: PreparedStatement,
+    prepared_scan: PreparedStatement,
+    prepared_lwt_insert: PreparedStatement,
+    prepared_lwt_update: PreparedStatement,
+    prepared_counter_update: PreparedStatement,
+    prepared_counter_select: PreparedStatement,
+    prepared_insert_point: PreparedStatement,
+    prepared_select_points: PreparedStatement,
+    // `config.statements_per_batch` inserts, only the values change between batches
+    batch: Batch,
+}
+
+#[async_trait]
+impl Driver for ScyllaDriver {
+    type Session = Session;
+
+    const NAME: &'static str = "scylla-rust-driver";
+    const DEFAULT_NODES: &'static str = "127.0.0.1:9042";
+
+    async fn connect(config: &Config) -> Result<Session> {
+        // The driver falls back to uncompressed frames when the nodes don't support the requested
+        // compression and doesn't expose which one is used, so it isn't reported
+        let mut builder = SessionBuilder::new()
+            .known_nodes(&config.node_addresses)
+            .compression(to_scylla_compression(config.compression))
+            .load_balancing(load_balancing_policy(&Self::load_balancing(config)?));
+        if config.tls {
+            builder = builder.ssl_context(Some(ssl_context(config)?));
+        }
+        if let (Some(user), Some(password)) = (&config.user, &config.password) {
+            builder = builder.user(user, password.as_str());
+        }
+        if let Some(pool_size) = pool_size(config) {
+            builder = builder.pool_size(pool_size);
+        }
+
+        let session: Session = builder.build().await?;
+
+        Ok(session)
+    }
+
+    // Requests are always sent to the shard which owns the partition when its token is known,
+    // the driver has no way to turn it off
+    fn load_balancing(config: &Config) -> Result<LoadBalancingSettings> {
+        if config.shard_aware == Some(false) {
+            return Err(anyhow!(
+                "scylla-rust-driver is always shard aware, it can't be turned off"
+            ));
+        }
+
+        Ok(LoadBalancingSettings {
+            policy: config.load_balancing.unwrap_or(LoadBalancing::RoundRobin),
+            local_dc: config.local_dc.clone(),
+            token_aware: config.token_aware.unwrap_or(true),
+            shard_aware: true,
+        })
+    }
+
+    async fn create_schema(session: &Session, config: &Config) -> Result<()> {
+        session
+            .query(config.schema.drop_keyspace_query(), &[])
+            .await?;
 
-    let config: Arc<Config> = match Config::read()? {
-        Some(config) => Arc::new(config),
-        None => return Ok(()), // --help only prints usage
-    };
+        session.await_schema_agreement().await?;
 
-    println!("Benchmark configuration:\n{:#?}\n", config);
+        session
+            .query(config.schema.create_keyspace_query(), &[])
+            .await?;
 
-    let session: Session = SessionBuilder::new()
-        .known_nodes(&config.node_addresses)
-        .build()
+        session.await_schema_agreement().await?;
+
+        for create_table in config.schema.create_table_queries() {
+            session.query(create_table, &[]).await?;
+
+            session.await_schema_agreement().await?;
+        }
+
+        Ok(())
+    }
+
+    async fn prepare(session: Session, config: &Config) -> Result<ScyllaDriver> {
+        let prepared_insert =
+            prepare_statement(&session, config.schema.insert_query(), config).await?;
+        let prepared_select =
+            prepare_statement(&session, config.schema.select_query(), config).await?;
+        let prepared_update =
+            prepare_statement(&session, config.schema.update_query(), config).await?;
+        let prepared_delete =
+            prepare_statement(&session, config.schema.delete_query(), config).await?;
+        let prepared_insert_wide =
+            prepare_statement(&session, config.schema.insert_wide_query(), config).await?;
+        let mut prepared_scan =
+            prepare_statement(&session, config.schema.scan_query(), config).await?;
+        prepared_scan.set_page_size(config.page_size);
+        let prepared_lwt_insert =
+            prepare_statement(&session, config.schema.lwt_insert_query(), config).await?;
+        let prepared_lwt_update =
+            prepare_statement(&session, config.schema.lwt_update_query(), config).await?;
+        let prepared_counter_update =
+            prepare_statement(&session, config.schema.counter_update_query(), config).await?;
+        let prepared_counter_select =
+            prepare_statement(&session, config.schema.counter_select_query(), config).await?;
+        let prepared_insert_point = prepare_statement(
+            &session,
+            config.schema.timeseries_insert_query(config.ttl),
+            config,
+        )
         .await?;
+        let prepared_select_points =
+            prepare_statement(&session, config.schema.timeseries_select_query(), config).await?;
+
+        let batch_type = match config.batch_type {
+            BatchType::Logged => ScyllaBatchType::Logged,
+            BatchType::Unlogged => ScyllaBatchType::Unlogged,
+        };
+        let batch_insert = match config.same_partition_batches {
+            true => &prepared_insert_wide,
+            false => &prepared_insert,
+        };
+
+        let mut batch = Batch::new(batch_type);
+        if let Some(consistency) = config.consistency {
+            batch.set_consistency(to_scylla_consistency(consistency));
+        }
+        if let Some(serial_consistency) = config.serial_consistency {
+            batch.set_serial_consistency(Some(to_scylla_serial_consistency(serial_consistency)));
+        }
+        for _ in 0..config.statements_per_batch {
+            batch.append_statement(batch_insert.clone());
+        }
+
+        Ok(ScyllaDriver {
+            session,
+            prepared_insert,
+            prepared_select,
+            prepared_update,
+            prepared_delete,
+            prepared_insert_wide,
+            prepared_scan,
+            prepared_lwt_insert,
+            prepared_lwt_update,
+            prepared_counter_update,
+            prepared_counter_select,
+            prepared_insert_point,
+            prepared_select_points,
+            batch,
+        })
+    }
+
+    async fn insert(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 1);
+        serialized.add_value(&pk)?;
+        add_values(&mut serialized, values)?;
 
-    let session = Arc::new(session);
+        self.session
+            .execute(&self.prepared_insert, serialized)
+            .await?;
 
-    if !config.dont_prepare {
-        prepare_keyspace_and_table(&session).await?;
+        Ok(())
     }
 
-    let insert_stmt = "INSERT INTO benchks.benchtab (pk, v1, v2) VALUES(?, ?, ?)";
-    let select_stmt = "SELECT v1, v2 FROM benchks.benchtab WHERE pk = ?";
+    async fn select(&self, pk: i64) -> Result<Option<Vec<Value>>> {
+        let row = self
+            .session
+            .execute(&self.prepared_select, (pk,))
+            .await?
+            .rows
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?
+            .into_iter()
+            .next();
+
+        match row {
+            Some(row) => Ok(Some(
+                row.columns
+                    .into_iter()
+                    .map(to_value)
+                    .collect::<Result<_>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    async fn update(&self, pk: i64, values: &[Value]) -> Result<()> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 1);
+        add_values(&mut serialized, values)?;
+        serialized.add_value(&pk)?;
+
+        self.session
+            .execute(&self.prepared_update, serialized)
+            .await?;
 
-    let prepared_insert = Arc::new(session.prepare(insert_stmt).await?);
-    let prepared_select = Arc::new(session.prepare(select_stmt).await?);
+        Ok(())
+    }
+
+    async fn delete(&self, pk: i64) -> Result<()> {
+        self.session.execute(&self.prepared_delete, (pk,)).await?;
 
-    if config.workload == Workload::Selects && !config.dont_prepare {
-        prepare_selects_benchmark(&session, &prepared_insert, &config).await?;
+        Ok(())
     }
 
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
+    async fn insert_wide(&self, pk: i64, ck: i64, values: &[Value]) -> Result<()> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 2);
+        serialized.add_value(&pk)?;
+        serialized.add_value(&ck)?;
+        add_values(&mut serialized, values)?;
 
-    println!("\nStarting the benchmark");
+        self.session
+            .execute(&self.prepared_insert_wide, serialized)
+            .await?;
 
-    let start_time = std::time::Instant::now();
+        Ok(())
+    }
 
-    for _ in 0..config.concurrency {
-        let session = session.clone();
-        let prepared_insert = prepared_insert.clone();
-        let prepared_select = prepared_select.clone();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
+    async fn scan(&self, pk: i64) -> Result<i64> {
+        // The iterator fetches the next page when the current one runs out
+        let mut rows = self
+            .session
+            .execute_iter(self.prepared_scan.clone(), (pk,))
+            .await?;
+
+        let mut row_count: i64 = 0;
+        while let Some(row) = rows.next().await {
+            for column in row?.columns {
+                to_value(column)?;
+            }
+            row_count += 1;
+        }
 
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
+        Ok(row_count)
+    }
 
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+    async fn batch(&self, inserts: &[BatchInsert]) -> Result<()> {
+        let mut values: Vec<SerializedValues> = Vec::with_capacity(inserts.len());
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
-
-                for pk in cur_batch_start..cur_batch_end {
-                    if config.workload == Workload::Inserts || config.workload == Workload::Mixed {
-                        session
-                            .execute(&prepared_insert, (pk, 2 * pk, 3 * pk))
-                            .await
-                            .unwrap();
-                    }
-
-                    if config.workload == Workload::Selects || config.workload == Workload::Mixed {
-                        let (v1, v2): (i64, i64) = session
-                            .execute(&prepared_select, (pk,))
-                            .await
-                            .unwrap()
-                            .rows
-                            .unwrap()
-                            .into_typed::<(i64, i64)>()
-                            .next()
-                            .unwrap()
-                            .unwrap();
-
-                        assert_eq!((v1, v2), (2 * pk, 3 * pk));
-                    }
-                }
+        for insert in inserts {
+            let mut serialized = SerializedValues::with_capacity(insert.values.len() + 2);
+            serialized.add_value(&insert.pk)?;
+            if let Some(ck) = insert.ck {
+                serialized.add_value(&ck)?;
             }
-        }));
+            add_values(&mut serialized, &insert.values)?;
+
+            values.push(serialized);
+        }
+
+        self.session.batch(&self.batch, values).await?;
+
+        Ok(())
+    }
+
+    async fn lwt_insert(&self, pk: i64, values: &[Value]) -> Result<bool> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 1);
+        serialized.add_value(&pk)?;
+        add_values(&mut serialized, values)?;
+
+        let result = self
+            .session
+            .execute(&self.prepared_lwt_insert, serialized)
+            .await?;
+
+        applied(result)
     }
 
-    for handle in handles {
-        handle.await?;
+    async fn lwt_update(&self, pk: i64, values: &[Value], expected: &Value) -> Result<bool> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 2);
+        add_values(&mut serialized, values)?;
+        serialized.add_value(&pk)?;
+        add_values(&mut serialized, std::slice::from_ref(expected))?;
+
+        let result = self
+            .session
+            .execute(&self.prepared_lwt_update, serialized)
+            .await?;
+
+        applied(result)
     }
 
-    let bench_time = start_time.elapsed();
-    println!("Finished\n\nBenchmark time: {} ms", bench_time.as_millis());
+    async fn counter_update(&self, pk: i64, increment: i64) -> Result<()> {
+        self.session
+            .execute(&self.prepared_counter_update, (Counter(increment), pk))
+            .await?;
 
-    Ok(())
+        Ok(())
+    }
+
+    async fn counter_select(&self, pk: i64) -> Result<Option<i64>> {
+        let row = self
+            .session
+            .execute(&self.prepared_counter_select, (pk,))
+            .await?
+            .rows
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?
+            .into_iter()
+            .next();
+
+        match row.map(|row| row.columns.into_iter().next()) {
+            Some(Some(Some(CqlValue::Counter(Counter(value))))) => Ok(Some(value)),
+            Some(other) => Err(anyhow!("Unexpected counter value: {:?}", other)),
+            None => Ok(None),
+        }
+    }
+
+    async fn insert_point(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        ts: i64,
+        values: &[Value],
+    ) -> Result<()> {
+        let mut serialized = SerializedValues::with_capacity(values.len() + 3);
+        serialized.add_value(&sensor_id)?;
+        serialized.add_value(&day)?;
+        serialized.add_value(&Timestamp(chrono::Duration::milliseconds(ts)))?;
+        add_values(&mut serialized, values)?;
+
+        self.session
+            .execute(&self.prepared_insert_point, serialized)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn select_points(
+        &self,
+        sensor_id: i64,
+        day: i64,
+        limit: i32,
+    ) -> Result<Vec<(i64, Vec<Value>)>> {
+        let rows = self
+            .session
+            .execute(&self.prepared_select_points, (sensor_id, day, limit))
+            .await?
+            .rows
+            .ok_or_else(|| anyhow!("Select didn't return any rows"))?;
+
+        let mut points = Vec::with_capacity(rows.len());
+        for row in rows {
+            let mut columns = row.columns.into_iter();
+            let ts = match columns.next() {
+                Some(Some(CqlValue::Timestamp(ts))) => ts.num_milliseconds(),
+                other => {
+                    return Err(error::verification_error(format!(
+                        "Unexpected ts value: {:?}",
+                        other
+                    )))
+                }
+            };
+            let values = columns.map(to_value).collect::<Result<_>>()?;
+            points.push((ts, values));
+        }
+
+        Ok(points)
+    }
+
+    fn classify_error(err: &anyhow::Error) -> ErrorKind {
+        match err.downcast_ref::<QueryError>() {
+            Some(QueryError::DbError(db_error, _)) => match db_error {
+                DbError::ReadTimeout { .. } | DbError::WriteTimeout { .. } => ErrorKind::Timeout,
+                DbError::Overloaded => ErrorKind::Overloaded,
+                DbError::Unavailable { .. } => ErrorKind::Unavailable,
+                _ => ErrorKind::Other,
+            },
+            Some(QueryError::TimeoutError) => ErrorKind::Timeout,
+            Some(QueryError::IoError(_)) => ErrorKind::Connection,
+            Some(_) => ErrorKind::Other,
+            None => error::classify_generic(err),
+        }
+    }
 }
 
-async fn prepare_keyspace_and_table(session: &Session) -> Result<()> {
-    session
-        .query("DROP KEYSPACE IF EXISTS benchks", &[])
-        .await?;
+// Peer verification of openssl is only turned on when there is a CA certificate to verify with
+fn ssl_context(config: &Config) -> Result<SslContext> {
+    let mut context = SslContextBuilder::new(SslMethod::tls())?;
 
-    session.await_schema_agreement().await?;
+    match &config.ca_cert {
+        Some(ca_cert) => {
+            context.set_ca_file(ca_cert)?;
+            context.set_verify(SslVerifyMode::PEER);
+        }
+        None => context.set_verify(SslVerifyMode::NONE),
+    }
 
-    session
-        .query(
-            "CREATE KEYSPACE IF NOT EXISTS benchks WITH REPLICATION = \
-            {'class' : 'SimpleStrategy', 'replication_factor' : 1}",
-            &[],
-        )
-        .await?;
+    if let (Some(client_cert), Some(client_key)) = (&config.client_cert, &config.client_key) {
+        context.set_certificate_file(client_cert, SslFiletype::PEM)?;
+        context.set_private_key_file(client_key, SslFiletype::PEM)?;
+    }
 
-    session.await_schema_agreement().await?;
+    Ok(context.build())
+}
 
-    session
-        .query(
-            "CREATE TABLE IF NOT EXISTS benchks.benchtab (pk bigint PRIMARY KEY, v1 bigint, v2 bigint)",
-            &[],
-        )
-        .await?;
+// Prepares the query with the consistency levels from the configuration
+async fn prepare_statement(
+    session: &Session,
+    query: String,
+    config: &Config,
+) -> Result<PreparedStatement> {
+    let mut prepared = session.prepare(query).await?;
 
-    session.await_schema_agreement().await?;
+    if let Some(consistency) = config.consistency {
+        prepared.set_consistency(to_scylla_consistency(consistency));
+    }
+    if let Some(serial_consistency) = config.serial_consistency {
+        prepared.set_serial_consistency(Some(to_scylla_serial_consistency(serial_consistency)));
+    }
 
-    Ok(())
+    Ok(prepared)
 }
 
-async fn prepare_selects_benchmark(
-    session: &Arc<Session>,
-    prepared_insert: &Arc<PreparedStatement>,
-    config: &Arc<Config>,
-) -> Result<()> {
-    println!("Preparing a selects benchmark (inserting values)...");
-
-    let mut handles = Vec::with_capacity(config.concurrency.try_into().unwrap());
-    let next_batch_start = Arc::new(AtomicI64::new(0));
-
-    for _ in 0..std::cmp::max(1024, config.concurrency) {
-        let session = session.clone();
-        let prepared_insert = prepared_insert.clone();
-        let config = config.clone();
-        let next_batch_start = next_batch_start.clone();
-
-        handles.push(tokio::spawn(async move {
-            loop {
-                let cur_batch_start: i64 =
-                    next_batch_start.fetch_add(config.batch_size, Ordering::Relaxed);
-
-                if cur_batch_start >= config.tasks {
-                    // No more work to do
-                    break;
-                }
+fn pool_size(config: &Config) -> Option<PoolSize> {
+    match (config.connections_per_host, config.connections_per_shard) {
+        (Some(connections), _) => NonZeroUsize::new(connections as usize).map(PoolSize::PerHost),
+        (_, Some(connections)) => NonZeroUsize::new(connections as usize).map(PoolSize::PerShard),
+        (None, None) => None,
+    }
+}
+
+// Token aware round robin is the default policy of the driver
+fn load_balancing_policy(settings: &LoadBalancingSettings) -> Arc<dyn LoadBalancingPolicy> {
+    let local_dc: String = settings.local_dc.clone().unwrap_or_default();
+
+    match (settings.policy, settings.token_aware) {
+        (LoadBalancing::RoundRobin, false) => Arc::new(RoundRobinPolicy::new()),
+        (LoadBalancing::RoundRobin, true) => {
+            Arc::new(TokenAwarePolicy::new(Box::new(RoundRobinPolicy::new())))
+        }
+        (LoadBalancing::DcAware, false) => Arc::new(DcAwareRoundRobinPolicy::new(local_dc)),
+        (LoadBalancing::DcAware, true) => Arc::new(TokenAwarePolicy::new(Box::new(
+            DcAwareRoundRobinPolicy::new(local_dc),
+        ))),
+    }
+}
+
+fn to_scylla_compression(compression: Compression) -> Option<ScyllaCompression> {
+    match compression {
+        Compression::None => None,
+        Compression::Lz4 => Some(ScyllaCompression::Lz4),
+        Compression::Snappy => Some(ScyllaCompression::Snappy),
+    }
+}
+
+fn to_scylla_consistency(consistency: Consistency) -> ScyllaConsistency {
+    match consistency {
+        Consistency::Any => ScyllaConsistency::Any,
+        Consistency::One => ScyllaConsistency::One,
+        Consistency::Two => ScyllaConsistency::Two,
+        Consistency::Three => ScyllaConsistency::Three,
+        Consistency::Quorum => ScyllaConsistency::Quorum,
+        Consistency::All => ScyllaConsistency::All,
+        Consistency::LocalQuorum => ScyllaConsistency::LocalQuorum,
+        Consistency::EachQuorum => ScyllaConsistency::EachQuorum,
+        Consistency::LocalOne => ScyllaConsistency::LocalOne,
+    }
+}
+
+fn to_scylla_serial_consistency(serial_consistency: SerialConsistency) -> ScyllaSerialConsistency {
+    match serial_consistency {
+        SerialConsistency::Serial => ScyllaSerialConsistency::Serial,
+        SerialConsistency::LocalSerial => ScyllaSerialConsistency::LocalSerial,
+    }
+}
+
+fn add_values(serialized: &mut SerializedValues, values: &[Value]) -> Result<()> {
+    for value in values {
+        match value {
+            Value::Bigint(v) => serialized.add_value(v)?,
+            Value::Text(v) => serialized.add_value(v)?,
+            Value::Blob(v) => serialized.add_value(v)?,
+            Value::Uuid(v) => serialized.add_value(&Uuid::from_bytes(*v))?,
+            Value::Timestamp(v) => {
+                serialized.add_value(&Timestamp(chrono::Duration::milliseconds(*v)))?
+            }
+            Value::List(v) => serialized.add_value(v)?,
+            // A Vec is sent as a list, which has the same encoding as a set
+            Value::Set(v) => serialized.add_value(v)?,
+            Value::Map(v) => {
+                serialized.add_value(&v.iter().cloned().collect::<BTreeMap<_, _>>())?
+            }
+            Value::Udt { id, name } => serialized.add_value(&BenchUdt {
+                id: *id,
+                name: name.clone(),
+            })?,
+        }
+    }
 
-                let cur_batch_end: i64 =
-                    std::cmp::min(cur_batch_start + config.batch_size, config.tasks);
+    Ok(())
+}
 
-                for pk in cur_batch_start..cur_batch_end {
-                    session
-                        .execute(&prepared_insert, (pk, 2 * pk, 3 * pk))
-                        .await
-                        .unwrap();
+fn to_value(cql_value: Option<CqlValue>) -> Result<Value> {
+    match cql_value {
+        Some(CqlValue::BigInt(v)) => Ok(Value::Bigint(v)),
+        Some(CqlValue::Text(v)) => Ok(Value::Text(v)),
+        Some(CqlValue::Blob(v)) => Ok(Value::Blob(v)),
+        Some(CqlValue::Uuid(v)) => Ok(Value::Uuid(*v.as_bytes())),
+        Some(CqlValue::Timestamp(v)) => Ok(Value::Timestamp(v.num_milliseconds())),
+        Some(CqlValue::List(elements)) => Ok(Value::List(
+            elements
+                .into_iter()
+                .map(|element| match element {
+                    CqlValue::Int(v) => Ok(v),
+                    other => Err(error::verification_error(format!(
+                        "Unexpected list element: {:?}",
+                        other
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+        )),
+        Some(CqlValue::Set(elements)) => Ok(Value::Set(
+            elements
+                .into_iter()
+                .map(|element| match element {
+                    CqlValue::Text(v) => Ok(v),
+                    other => Err(error::verification_error(format!(
+                        "Unexpected set element: {:?}",
+                        other
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+        )),
+        Some(CqlValue::Map(entries)) => Ok(Value::Map(
+            entries
+                .into_iter()
+                .map(|entry| match entry {
+                    (CqlValue::Text(key), CqlValue::BigInt(value)) => Ok((key, value)),
+                    other => Err(error::verification_error(format!(
+                        "Unexpected map entry: {:?}",
+                        other
+                    ))),
+                })
+                .collect::<Result<_>>()?,
+        )),
+        Some(CqlValue::UserDefinedType { fields, .. }) => {
+            let mut fields = fields.into_iter();
+            match (fields.next(), fields.next()) {
+                (Some((_, Some(CqlValue::BigInt(id)))), Some((_, Some(CqlValue::Text(name))))) => {
+                    Ok(Value::Udt { id, name })
                 }
+                other => Err(error::verification_error(format!(
+                    "Unexpected udt fields: {:?}",
+                    other
+                ))),
             }
-        }));
+        }
+        None => Err(error::verification_error("Unexpected null column")),
+        Some(other) => Err(error::verification_error(format!(
+            "Unexpected column value: {:?}",
+            other
+        ))),
     }
+}
 
-    for handle in handles {
-        handle.await?;
+// When the condition fails the row also holds the current values, only `[applied]` is read
+fn applied(result: QueryResult) -> Result<bool> {
+    let row = result
+        .rows
+        .and_then(|rows| rows.into_iter().next())
+        .ok_or_else(|| anyhow!("Conditional statement didn't return the [applied] column"))?;
+
+    match row.columns.into_iter().next() {
+        Some(Some(CqlValue::Boolean(applied))) => Ok(applied),
+        other => Err(anyhow!("Unexpected [applied] value: {:?}", other)),
     }
+}
 
-    Ok(())
+#[tokio::main]
+async fn main() -> Result<()> {
+    bench_core::run::<ScyllaDriver>().await
 }